@@ -7,11 +7,14 @@ use example_consumer::benches::query_builder_string::query_builder_string_bench;
 use example_consumer::benches::static_string::static_string_bench;
 use example_consumer::benches::query_builder_query::query_builder_query_bench;
 use example_consumer::benches::static_query::static_query_bench;
+use example_consumer::benches::prepared_query::{prepare_page_query, prepared_query_string_bench, prepared_query_query_bench};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     let rt = tokio::runtime::Runtime::new().unwrap();
     c.bench_function("ss", |b| b.iter(|| static_string_bench()));
     c.bench_function("qbs", |b| b.iter(|| query_builder_string_bench()));
+    let (mut prepared, page, component) = prepare_page_query();
+    c.bench_function("pqs", |b| b.iter(|| prepared_query_string_bench(&mut prepared, &page, &component)));
     c.bench_function("sq", |b| {
         b.to_async(&rt).iter(|| async {
             static_query_bench().await;
@@ -32,6 +35,13 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             query_builder_query_bench().await;
         });
     });
+    // The prepared query is built once and rebound on every iteration, behind a lock as the async closure can't hold &mut.
+    let prepared = tokio::sync::Mutex::new(prepared);
+    c.bench_function("pqq", |b| {
+        b.to_async(&rt).iter(|| async {
+            prepared_query_query_bench(&mut *prepared.lock().await, &page, &component).await;
+        });
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
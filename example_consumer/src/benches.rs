@@ -1,4 +1,5 @@
 pub mod query_builder_string;
 pub mod static_string;
 pub mod query_builder_query;
-pub mod static_query;
\ No newline at end of file
+pub mod static_query;
+pub mod prepared_query;
\ No newline at end of file
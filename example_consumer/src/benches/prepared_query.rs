@@ -0,0 +1,39 @@
+use crate::entity_wrapper::EntityWrapper;
+use crate::objects::{Page, PageProps, Component, ComponentProps, ComponentType, HasComponent};
+use crate::benches::query_builder_query::connect_neo4j;
+use neo4g::query_builder::{self, CompareOperator, CompOper, Neo4gBuilder, Where};
+use neo4g::prepared_query::PreparedQuery;
+use neo4g::traits::WrappedNeo4gEntity;
+use neo4g_macro_rules::{no_props, props};
+
+pub fn prepare_page_query() -> (PreparedQuery, Page, Component) {
+    let mut component1 = Component::new("cid3", "path3", ComponentType::Type1, false);
+    let mut hcrel1 = HasComponent::default();
+    let mut page1 = Page::new("pid4", "ppath4", Vec::new(), false);
+    let prepared = Neo4gBuilder::new()
+        .get()
+            .node(&mut page1, props!(page1 => page1.id)).add_to_return()
+            .relation(&mut hcrel1, no_props!())
+            .node(&mut component1, props!(component1 => component1.id))
+            .filter(Where::new()
+                .condition_prop(&page1, Some(&page1.id), CompareOperator::by_prop(CompOper::Eq, &page1.id, query_builder::RefType::Val))
+            )
+        .end_statement()
+        .prepare();
+    (prepared, page1, component1)
+}
+
+pub fn prepared_query_string_bench(prepared: &mut PreparedQuery, page: &Page, component: &Component) {
+    prepared
+        .bind_for(page, PageProps::Id("pid4".into())).unwrap()
+        .bind_for(component, ComponentProps::Id("cid73".into())).unwrap();
+    let _result = prepared.build();
+}
+
+pub async fn prepared_query_query_bench(prepared: &mut PreparedQuery, page: &Page, component: &Component) {
+    let graph = connect_neo4j().await;
+    prepared
+        .bind_for(page, PageProps::Id("pid4".into())).unwrap()
+        .bind_for(component, ComponentProps::Id("cid73".into())).unwrap();
+    let _result = prepared.run_query(graph, EntityWrapper::from_db_entity).await;
+}
\ No newline at end of file
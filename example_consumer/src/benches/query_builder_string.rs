@@ -2,18 +2,18 @@ use crate::entity_wrapper::{EntityWrapper, Label};
 use crate::objects::{Group, GroupProps, MemberOf, MemberOfProps, User, UserProps, UserTemplate, Page, PageProps, PageTemplate, Component, ComponentProps, ComponentTemplate, ComponentType, HasComponent, HasComponentTemplate, HasComponentProps};
 use neo4g::query_builder::{self, CompareJoiner, CompareOperator, CompOper, Neo4gBuilder, Where};
 use neo4g_macro_rules::{no_props, prop, props};
-use chrono::{NaiveDateTime, Utc};
+use chrono::NaiveDateTime;
 use neo4rs::Graph;
 use dotenv::dotenv;
 use std::{env, result};
 use heck::ToShoutySnakeCase;
 
 pub fn query_builder_string_bench() {
-    let mut component1 = Component::new("cid3", "path3", ComponentType::Type1, Utc::now().naive_local(), Utc::now().naive_local(), false);
-    let mut component2 = Component::new("cid73", "path16", ComponentType::Type2, Utc::now().naive_local(), Utc::now().naive_local(), false);
+    let mut component1 = Component::new("cid3", "path3", ComponentType::Type1, false);
+    let mut component2 = Component::new("cid73", "path16", ComponentType::Type2, false);
     let mut hcrel1 = HasComponent::default();
     let mut hcrel2 = HasComponent::default();
-    let mut page1 = Page::new("pid4", "ppath4", vec![component1.clone().into(), component2.clone().into()], Utc::now().naive_local(), Utc::now().naive_local(), false);
+    let mut page1 = Page::new("pid4", "ppath4", vec![component1.clone().into(), component2.clone().into()], false);
     let result = Neo4gBuilder::new()
         .get()
             .node(&mut page1, props!(page1 => page1.id))
@@ -1,3 +1,4 @@
 pub mod entity_wrapper;
 pub mod objects;
-pub mod benches;
\ No newline at end of file
+pub mod benches;
+pub mod migrations;
\ No newline at end of file
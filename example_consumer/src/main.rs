@@ -1,4 +1,4 @@
-use example_consumer::entity_wrapper::{EntityWrapper, Label, ValueProps};
+use example_consumer::entity_wrapper::EntityWrapper;
 use example_consumer::objects::{Group, GroupProps, MemberOf, MemberOfProps, User, UserProps, UserTemplate, Page, PageProps, PageTemplate, Component, ComponentProps, ComponentTemplate, ComponentType, HasComponent, HasComponentTemplate, HasComponentProps};
 
 use dotenv::dotenv; 
@@ -27,83 +27,51 @@ pub async fn connect_neo4j() -> Graph { //return db object, run on startup, bind
 #[tokio::main]
 async fn main() {
     let graph = connect_neo4j().await;
-    let mut component1 = Component::new("cid3", "path3sadf", ComponentType::Type1, Utc::now().naive_local(), Utc::now().naive_local(), false);
-    let mut component2 = Component::new("cid4", "path4", ComponentType::Type2, Utc::now().naive_local(), Utc::now().naive_local(), false);
+    Neo4gSchema::ensure(&graph, &[User::schema(), Group::schema(), Page::schema(), Component::schema()]).await.unwrap();
+    example_consumer::migrations::migrations().up(&graph).await.unwrap();
+    let mut component1 = Component::new("cid3", "path3sadf", ComponentType::Type1, false);
+    let mut component2 = Component::new("cid4", "path4", ComponentType::Type2, false);
     let mut hcrel1 = HasComponent::default();
     let mut hcrel2 = HasComponent::default();
-    let mut page1 = Page::new("pid4", "p1sadfpath234", vec![component1.clone().into(), component2.clone().into()], Utc::now().naive_local(), Utc::now().naive_local(), false);
-    let mut page2 = Page::new("pid99", "DID IT WORK?!", Vec::new(), Utc::now().naive_local(), Utc::now().naive_local(), false);
-    let mut page3 = Page::new("pid6", "DID IT WORK?!", Vec::new(), Utc::now().naive_local(), Utc::now().naive_local(), false);
+    let mut page1 = Page::new("pid4", "p1sadfpath234", vec![component1.clone().into(), component2.clone().into()], false);
+    let mut page2 = Page::new("pid99", "DID IT WORK?!", Vec::new(), false);
+    let mut page3 = Page::new("pid6", "DID IT WORK?!", Vec::new(), false);
     let mut array1 = Array::new("array1", vec!["cid3".into(), "cid4".into()]);
     let mut collect_page2 = FunctionCall::from(Function::Collect(Box::new(Expr::from(&page2))));
-    let mut groups = Array::new("member_ofs", vec!["582bb0b6-5e9e-4a5e-90ba-9d9a97410166".into(), "e4957a65-4dd2-4c74-b356-a271d6c0982b".into()]);
-    let mut user = User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "8f327a097ce4b035bd0425c9782f756c4b3e6a080bae8ad2b139cbc6c31e6575", "system3", "user3", Vec::new(), Utc::now().naive_local(), Utc::now().naive_local(), false);
-    let mut member_of = MemberOf::new(Utc::now().naive_local(), Utc::now().naive_local(), false);
+    let groups: Vec<Group> = ["582bb0b6-5e9e-4a5e-90ba-9d9a97410166", "e4957a65-4dd2-4c74-b356-a271d6c0982b"].iter().map(|id| {
+        Group { id: GroupProps::Id(id.to_string()), ..Default::default() }
+    }).collect();
+    let mut user = User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "8f327a097ce4b035bd0425c9782f756c4b3e6a080bae8ad2b139cbc6c31e6575", "system3", "user3", Vec::new(), false);
+    let member_of = MemberOf::new(false);
     //let mut collect_page2 = FunctionCall::from(Function::Coalesce(vec![Expr::from(Function::Id(Box::new(Expr::from(&page2)))), Expr::from(&page3)]));
-    let mut unwound_groups_option_match = Group::default();
-    let mut size_groups_fn = Function::Size(Box::new(Expr::from(&groups)));
-    let mut unwound_groups = Unwinder::new(&groups);
-    let mut collect_groups = FunctionCall::from(Function::Collect(Box::new(Expr::from(&unwound_groups_option_match))));
 
     //complex, real-world query test
     let result = Neo4gBuilder::new()
         .merge()
-            .node(&mut user, props!(user => user.name))
+            .node(&mut user, props!(user => user.name)).add_to_return()
             .on_create()
                 .set(&user, props!(user => user.name, user.forename, user.surname, user.password, user.deleted))
             .on_match()
                 .set(&user, props!(user => user.forename, user.surname, user.password))
         .end_statement()
-        .with()
-            .entities(&[user.wrap()])
-            .arrays(arrays![groups])
-        .call_with(&[user.wrap(), EntityWrapper::Array(groups.clone())], |inner| {
-            inner
-                .with()
-                    .entities(wrap![user])
-                    .arrays(arrays![groups])
-                    .filter(Where::new()
-                        .condition(&size_groups_fn, CompareOperator::by_prop(CompOper::Gt, &ValueProps::Int(0), RefType::Val))
-                    )
-                .unwind(&mut unwound_groups)
-                .optional_match()
-                    .nodes_by_unwound(&mut unwound_groups_option_match, prop!(unwound_groups_option_match.id), &unwound_groups)
-                .end_statement()
-                .with()
-                    .entities(wrap![user, unwound_groups_option_match])
-                    .filter(Where::new().is_not_null(&unwound_groups_option_match))
-                .merge()
-                    .node_ref(&user)
-                    .relation(&mut member_of, no_props!())
-                    .node_ref(&unwound_groups_option_match)
-                    .on_create()
-                        .set(&member_of, props!(member_of => member_of.created, member_of.updated, member_of.deleted))
-                .end_statement()
-                .with()
-                    .entities(wrap![user])
-                    .function(&mut collect_groups)
-                .optional_match()
-                    .node_ref(&user)
-                    .relation(&mut member_of, no_props!())
-                    .node_ref(&unwound_groups_option_match)
-                    .filter(Where::new()
-                        .not()
-                        .condition(&unwound_groups_option_match, CompareOperator::by_aliasable(CompOper::In, &collect_groups))
-                    )
-                    .delete(wrap![member_of], false)
-                .end_statement()
-        })
-        .get()
-            .node(&mut user, props!(user => user.name)).add_to_return()
-        .end_statement()
         .run_query(graph.clone(), EntityWrapper::from_db_entity).await;
                 
     println!("{:?}", result);
 
+    // Merges MemberOf relations to the listed groups and soft deletes the rest, as MemberOf has a soft_delete field.
+    let report = Neo4gBuilder::new()
+        .sync_relations(&user, prop!(user.id), &member_of, &groups, prop!(group.id), SyncOptions::default())
+        .run_query(graph.clone()).await;
+
+    println!("{:?}", report);
+
+    let admin = User::find_by(&graph, UserProps::Name("admin".to_string())).await;
+    println!("{:?}", admin);
+
 
-    let mut user = User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "8f327a097ce4b035bd0425c9782f756c4b3e6a080bae8ad2b139cbc6c31e6575", "system3", "user3", Vec::new(), Utc::now().naive_local(), Utc::now().naive_local(), false);
-    let mut group = Group::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f6", "default_access", Utc::now().naive_local(), Utc::now().naive_local(), false);
-    let mut member_of = MemberOf::new(Utc::now().naive_local(), Utc::now().naive_local(), false);
+    let mut user = User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "8f327a097ce4b035bd0425c9782f756c4b3e6a080bae8ad2b139cbc6c31e6575", "system3", "user3", Vec::new(), false);
+    let mut group = Group::new("default_access", false);
+    let mut member_of = MemberOf::new(false);
     let result = Neo4gBuilder::new()
         .get()
             .node(&mut user, no_props!()).add_to_return()
@@ -0,0 +1,12 @@
+use neo4g::prelude::*;
+
+/// The migrations shipped with this release, applied in version order by Migrator::up.
+pub fn migrations() -> Migrator {
+    Migrator::new(vec![
+        Migration::new(1, "default deleted flag")
+            .cypher("MATCH (u:User) WHERE u.deleted IS NULL SET u.deleted = false")
+            .cypher("MATCH (p:Page) WHERE p.deleted IS NULL SET p.deleted = false"),
+        Migration::new(2, "rename page url to path")
+            .cypher("MATCH (p:Page) WHERE p.url IS NOT NULL SET p.path = p.url REMOVE p.url"),
+    ])
+}
@@ -1,32 +1,47 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
 use neo4g::prelude::*;
 pub use neo4g_derive::*;
 use crate::entity_wrapper::{EntityWrapper, Nothing};
 
 #[derive(Neo4gNode, Serialize, Deserialize, Debug, Clone)]
+#[neo4g(repository)]
 pub struct GroupTemplate {
+    #[neo4g(key, id = "uuid_v4")]
     id: String,
     name: String,
+    #[neo4g(created_at)]
     created: NaiveDateTime,
+    #[neo4g(updated_at)]
     updated: NaiveDateTime,
+    #[neo4g(soft_delete)]
     deleted: bool,
 }
 
 #[derive(Neo4gRelation, Serialize, Deserialize, Debug, Clone)]
 pub struct MemberOfTemplate {
+    #[neo4g(created_at)]
     created: NaiveDateTime,
+    #[neo4g(updated_at)]
     updated: NaiveDateTime,
+    #[neo4g(soft_delete)]
     deleted: bool,
 }
 
 #[derive(Neo4gRelation, Serialize, Deserialize, Debug, Clone)]
 pub struct HasComponentTemplate {
+    #[neo4g(created_at)]
     created: NaiveDateTime,
+    #[neo4g(updated_at)]
     updated: NaiveDateTime,
+    #[neo4g(soft_delete)]
     deleted: bool,
 }
 
 #[derive(Neo4gNode, Serialize, Deserialize, Debug, Clone)] //
+#[neo4g(repository, hooks(before_save))]
 pub struct UserTemplate {
+    #[neo4g(key)]
     id: String,
     name: String,
     #[serde(skip)]
@@ -36,8 +51,11 @@ pub struct UserTemplate {
     #[not_query_param]
     groups: Vec<GroupTemplate>,
     #[serde(skip)]
+    #[neo4g(created_at)]
     created: NaiveDateTime,
+    #[neo4g(updated_at)]
     updated: NaiveDateTime,
+    #[neo4g(soft_delete)]
     deleted: bool,
 }
 
@@ -50,21 +68,56 @@ pub enum ComponentType {
 
 #[derive(Neo4gNode, Serialize, Deserialize, Debug, Clone)]
 pub struct ComponentTemplate {
+    #[neo4g(unique)]
     id: String,
     path: String,
     component_type: ComponentType,
+    #[neo4g(created_at)]
     created: NaiveDateTime,
+    #[neo4g(updated_at)]
     updated: NaiveDateTime,
+    #[neo4g(soft_delete)]
     deleted: bool,
 }
 
 #[derive(Neo4gNode, Serialize, Deserialize, Debug, Clone)]
+#[neo4g(repository, hooks(before_save))]
 pub struct PageTemplate {
+    #[neo4g(unique)]
     id: String,
     path: String,
     #[not_query_param]
     components: Vec<ComponentTemplate>,
+    #[neo4g(version)]
+    version: i64,
+    #[neo4g(created_at)]
     created: NaiveDateTime,
+    #[neo4g(updated_at)]
     updated: NaiveDateTime,
+    #[neo4g(soft_delete)]
     deleted: bool,
 }
+
+impl BeforeSave for User {
+    /// Stores ids in lowercase, so uuids copied from other systems match the ones generated here,
+    /// and stores a hash of the password instead of the password.
+    fn before_save(&mut self) {
+        self.id = UserProps::Id(self.id().to_lowercase());
+        self.password = UserProps::Password(hash_password(&self.password()));
+    }
+}
+
+/// Stands in for a real password hash such as argon2, which this example doesn't depend on.
+pub fn hash_password(password: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    password.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+impl BeforeSave for Page {
+    /// Stores paths without a trailing slash, so "/home/" and "/home" are the same page.
+    fn before_save(&mut self) {
+        let path = self.path().trim_end_matches('/').to_string();
+        self.path = PageProps::Path(if path.is_empty() { "/".to_string() } else { path });
+    }
+}
@@ -0,0 +1,35 @@
+use example_consumer::objects::{Group, Page, User};
+use neo4g::query_builder::Neo4gBuilder;
+use neo4g_macro_rules::prop;
+
+#[test]
+fn merge_many_merges_on_the_given_key() {
+    let groups = vec![Group::new("default_access", false), Group::new("admins", false)];
+    let (query, params) = Neo4gBuilder::new().merge_many(&groups, prop!(group.name)).build().remove(0);
+    assert!(query.contains("MERGE (n:Group {name: row.name})"), "{}", query);
+    assert!(params.contains_key("rows"));
+}
+
+#[test]
+fn merge_many_renders_the_label_and_key_when_there_are_no_entities() {
+    let groups: Vec<Group> = Vec::new();
+    let query = Neo4gBuilder::new().merge_many(&groups, prop!(group.name)).to_cypher_pretty();
+    assert!(query.contains("MERGE (n:Group {name: row.name})"), "{}", query);
+    assert!(query.contains("n.updated = localdatetime()"), "{}", query);
+}
+
+#[test]
+fn merge_many_checks_versions_when_there_are_no_entities() {
+    let pages: Vec<Page> = Vec::new();
+    let query = Neo4gBuilder::new().merge_many(&pages, prop!(page.id)).to_cypher_pretty();
+    assert!(query.contains("MERGE (n:Page {id: row.id})"), "{}", query);
+    assert!(query.contains("n.version = row.version + 1"), "{}", query);
+}
+
+#[test]
+fn create_many_splits_rows_into_batches() {
+    let users: Vec<User> = (0..5).map(|i| User::new("", &format!("user{}", i), "hash", "first", "last", Vec::new(), false)).collect();
+    let chunks = Neo4gBuilder::new().create_many(&users).batch_size(2).build();
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks[0].0.starts_with("UNWIND $rows AS row\nCREATE (n:User)\nSET n = row"), "{}", chunks[0].0);
+}
@@ -0,0 +1,49 @@
+// Shared by the test binaries, each of which only uses some of it.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use example_consumer::objects::User;
+use neo4g::executor::Executor;
+use neo4g::query_builder::Result;
+use neo4rs::{BoltInteger, BoltList, BoltMap, BoltNode, BoltString, BoltType, Row};
+
+/// The user most tests build queries from.
+pub fn user() -> User {
+    User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "hash", "system", "user", Vec::new(), false)
+}
+
+/// The user as the database hands it back.
+pub fn user_node() -> BoltType {
+    let mut properties = BoltMap::new();
+    properties.put(BoltString::new("id"), "8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5".into());
+    properties.put(BoltString::new("name"), "admin".into());
+    properties.put(BoltString::new("forename"), "system".into());
+    properties.put(BoltString::new("surname"), "user".into());
+    properties.put(BoltString::new("updated"), NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_milli_opt(9, 30, 15, 125).unwrap().into());
+    properties.put(BoltString::new("deleted"), false.into());
+    let mut labels = BoltList::new();
+    labels.push("User".into());
+    BoltType::Node(BoltNode { id: BoltInteger::new(7), labels, properties })
+}
+
+/// An executor that records the queries and params it is given and never matches anything.
+#[derive(Default)]
+pub struct NoRows {
+    pub sent: Vec<(String, HashMap<String, BoltType>)>,
+}
+
+impl NoRows {
+    /// The queries sent so far, in order.
+    pub fn queries(&self) -> Vec<String> {
+        self.sent.iter().map(|(query, _)| query.clone()).collect()
+    }
+}
+
+impl Executor for NoRows {
+    async fn fetch_rows(&mut self, query: &str, params: &HashMap<String, BoltType>) -> Result<Vec<Row>> {
+        self.sent.push((query.to_string(), params.clone()));
+        Ok(Vec::new())
+    }
+}
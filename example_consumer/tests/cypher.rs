@@ -0,0 +1,21 @@
+// Deliberately no neo4g prelude import: cypher! has to expand with fully qualified paths.
+use example_consumer::objects::{Group, MemberOf, User};
+use neo4g_derive::cypher;
+use neo4rs::BoltType;
+
+#[test]
+fn cypher_renders_the_query_and_reads_params_from_scope() {
+    let name = "admin".to_string();
+    let (query, params) = cypher!(MATCH (u: User {name: $name})-[m: MemberOf]->(g: Group) RETURN u, g);
+    assert_eq!(query, "MATCH (u:User {name: $name})-[m:MEMBER_OF]->(g:Group) RETURN u, g");
+    assert_eq!(params.len(), 1);
+    assert_eq!(params["name"], BoltType::from("admin"));
+}
+
+#[test]
+fn cypher_reads_untyped_params_from_scope() {
+    let limit = 10;
+    let (query, params) = cypher!(MATCH (u: User) WHERE u.forename = "system" RETURN u LIMIT $limit);
+    assert_eq!(query, "MATCH (u:User) WHERE u.forename = \"system\" RETURN u LIMIT $limit");
+    assert_eq!(params["limit"], BoltType::from(10));
+}
@@ -0,0 +1,52 @@
+use example_consumer::objects::{hash_password, User};
+use neo4g::prelude::BoltType;
+use neo4g::query_builder::Neo4gBuilder;
+use neo4g_macro_rules::props;
+
+/// Has an upper case id, which the BeforeSave hook lower cases.
+fn shouting_user() -> User {
+    User::new("8F8C54B6-5D22-45D6-9A24-DFACAA8D37F5", "admin", "hash", "system", "user", Vec::new(), false)
+}
+
+#[test]
+fn created_entities_are_hooked_in_place() {
+    let mut user = shouting_user();
+    let (_, params) = Neo4gBuilder::new()
+        .create()
+            .node(&mut user)
+        .end_statement()
+        .build();
+    assert_eq!(*user.password(), hash_password("hash"));
+    assert_eq!(*user.id(), "8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5");
+    assert_eq!(params.get("user1_password"), Some(&BoltType::from(hash_password("hash"))));
+}
+
+#[test]
+fn merged_entities_are_not_hooked_again_when_set() {
+    let mut user = shouting_user();
+    let (_, params) = Neo4gBuilder::new()
+        .merge()
+            .node(&mut user, props!(user => user.name))
+            .on_create().set(&user, props!(user => user.password))
+            .on_match().set(&user, props!(user => user.password))
+        .end_statement()
+        .build();
+    assert_eq!(*user.password(), hash_password("hash"));
+    assert_eq!(params.get("set_password1"), Some(&BoltType::from(hash_password("hash"))));
+    assert_eq!(params.get("set_password2"), Some(&BoltType::from(hash_password("hash"))));
+}
+
+#[test]
+fn matched_entities_are_hooked_on_a_copy_for_each_set() {
+    let mut user = shouting_user();
+    let (_, params) = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .set(&user, props!(user => user.password))
+            .set(&user, props!(user => user.password))
+        .end_statement()
+        .build();
+    assert_eq!(*user.password(), "hash");
+    assert_eq!(params.get("set_password1"), Some(&BoltType::from(hash_password("hash"))));
+    assert_eq!(params.get("set_password2"), Some(&BoltType::from(hash_password("hash"))));
+}
@@ -0,0 +1,35 @@
+use std::sync::{Arc, Mutex};
+
+use example_consumer::objects::{Group, User};
+use neo4g::interceptor::{clear_interceptors, register_interceptor, Interceptor, QueryContext};
+use neo4g::query_builder::Neo4gError;
+use neo4g::schema::Neo4gSchema;
+
+mod common;
+use common::NoRows;
+
+/// Records every query it sees and vetoes the ones on Group.
+struct VetoGroups(Arc<Mutex<Vec<String>>>);
+
+impl Interceptor for VetoGroups {
+    fn before(&self, context: &mut QueryContext) -> std::result::Result<(), String> {
+        self.0.lock().unwrap().push(context.query.clone());
+        if context.query.contains("(n:Group)") {
+            return Err("no group constraints".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Interceptors are global, so this is the only test in this file.
+#[tokio::test]
+async fn ensure_schema_runs_through_the_interceptors() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    register_interceptor(VetoGroups(seen.clone()));
+    let mut executor = NoRows::default();
+    let result = Neo4gSchema::ensure_with(&mut executor, &[User::schema(), Group::schema()]).await;
+    clear_interceptors();
+    assert!(matches!(result, Err(Neo4gError::Vetoed { reason }) if reason == "no group constraints"));
+    assert_eq!(seen.lock().unwrap().len(), 2);
+    assert_eq!(executor.queries(), User::schema().statements());
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use example_consumer::objects::{Component, ComponentType, Group, HasComponent, Page};
+use neo4g::migrations::Migration;
+use neo4g::query_builder::{self, CompOper, CompareOperator, Neo4gBuilder, Where};
+use neo4g_macro_rules::{no_props, props};
+use neo4rs::BoltType;
+
+fn page_migration() -> Migration {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let mut has_component = HasComponent::default();
+    let mut component = Component::new("cid1", "path1", ComponentType::Type1, false);
+    Migration::new(1, "page components")
+        .cypher_with_params("MATCH (p:Page) WHERE p.path = $path SET p.checked = $checked", HashMap::from([
+            ("path".to_string(), BoltType::from("/home")),
+            ("checked".to_string(), BoltType::from(true)),
+        ]))
+        .query(Neo4gBuilder::new()
+            .get()
+                .node(&mut page, no_props!()).add_to_return()
+                .relation(&mut has_component, no_props!()).add_to_return()
+                .node(&mut component, props!(component => component.id)).add_to_return()
+                .filter(Where::new()
+                    .condition_prop(&page, Some(&page.id), CompareOperator::by_prop(CompOper::Eq, &page.id, query_builder::RefType::Val))
+                )
+            .end_statement())
+}
+
+#[test]
+fn checksum_is_the_same_for_the_same_migration_built_twice() {
+    let first = page_migration().checksum();
+    for _ in 0..20 {
+        assert_eq!(page_migration().checksum(), first);
+    }
+}
+
+#[test]
+fn checksum_ignores_return_order() {
+    let a = Migration::new(1, "a").cypher("MATCH (a)-[r]->(b)\nRETURN a, r, b");
+    let b = Migration::new(1, "a").cypher("MATCH (a)-[r]->(b)\nRETURN b, a, r");
+    assert_eq!(a.checksum(), b.checksum());
+}
+
+#[test]
+fn checksum_ignores_param_values() {
+    let with = |value: i64| Migration::new(1, "a")
+        .cypher_with_params("MATCH (p:Page) SET p.rank = $rank", HashMap::from([("rank".to_string(), BoltType::from(value))]))
+        .checksum();
+    assert_eq!(with(1), with(2));
+}
+
+#[test]
+fn checksum_changes_when_a_param_type_changes() {
+    let with = |value: BoltType| Migration::new(1, "a")
+        .cypher_with_params("MATCH (p:Page) SET p.rank = $rank", HashMap::from([("rank".to_string(), value)]))
+        .checksum();
+    assert_ne!(with(BoltType::from(1)), with(BoltType::from("1")));
+}
+
+#[test]
+fn checksum_is_the_same_for_entities_with_generated_ids() {
+    let migration = || {
+        let mut group = Group::new("admins", false);
+        Migration::new(2, "admins group")
+            .query(Neo4gBuilder::new()
+                .create()
+                    .node(&mut group)
+                .end_statement())
+    };
+    assert_eq!(migration().checksum(), migration().checksum());
+}
+
+#[test]
+fn checksum_changes_when_the_query_changes() {
+    let a = Migration::new(1, "a").cypher("MATCH (p:Page) SET p.path = toLower(p.path)");
+    let b = Migration::new(1, "a").cypher("MATCH (p:Page) SET p.path = toUpper(p.path)");
+    assert_ne!(a.checksum(), b.checksum());
+}
@@ -0,0 +1,27 @@
+use example_consumer::objects::{Component, ComponentType};
+use neo4g::query_builder::Neo4gBuilder;
+
+mod common;
+use common::user;
+
+#[test]
+fn match_node_by_key_matches_on_the_key_field() {
+    let mut user = user();
+    let rendered = Neo4gBuilder::new()
+        .get()
+            .node_by_key(&mut user).add_to_return()
+        .end_statement()
+        .render();
+    assert!(rendered.query.starts_with("MATCH (user1:User {id: $user1_id"), "{}", rendered.query);
+}
+
+#[test]
+fn merge_node_by_key_merges_on_the_unique_field_when_there_is_no_key() {
+    let mut component = Component::new("cid1", "path1", ComponentType::Type1, false);
+    let rendered = Neo4gBuilder::new()
+        .merge()
+            .node_by_key(&mut component).add_to_return()
+        .end_statement()
+        .render();
+    assert!(rendered.query.starts_with("MERGE (component1:Component {id: $component1_id})"), "{}", rendered.query);
+}
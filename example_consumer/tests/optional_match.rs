@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use example_consumer::entity_wrapper::EntityWrapper;
+use example_consumer::objects::Group;
+use neo4g::query_builder::{optional, Neo4gBuilder, Neo4gError};
+use neo4g::replay::ReplayExecutor;
+use neo4g::traits::{MatchedNode, WrappedNeo4gEntity};
+use neo4g_macro_rules::{no_props, props};
+use neo4rs::BoltType;
+
+mod common;
+use common::{user, user_node};
+
+fn user_and_group(optional_group: bool) -> Neo4gBuilder<MatchedNode> {
+    let mut user = user();
+    let mut group = Group::new("admins", false);
+    let builder = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name)).add_to_return()
+        .end_statement();
+    let group_statement = if optional_group { builder.optional_match() } else { builder.get() };
+    group_statement
+        .node(&mut group, no_props!()).add_to_return()
+    .end_statement()
+}
+
+/// Answers the query with the user and a null group, as the database does when the group does not match.
+fn without_group(builder: &Neo4gBuilder<MatchedNode>) -> ReplayExecutor {
+    let mut replay = ReplayExecutor::new();
+    let row = HashMap::from([("user1".to_string(), user_node()), ("group2".to_string(), BoltType::Null(neo4rs::BoltNull))]);
+    replay.answer(&builder.clone().render().query, vec![row]);
+    replay
+}
+
+#[tokio::test]
+async fn a_null_optional_match_decodes_to_nothing() {
+    let builder = user_and_group(true);
+    let mut replay = without_group(&builder);
+    let mut rows = builder.run_with(&mut replay, EntityWrapper::from_db_entity).await.unwrap();
+    let row = rows.remove(0);
+    assert!(matches!(&row[0], EntityWrapper::User(user) if user.name() == "admin"));
+    assert!(matches!(&row[1], EntityWrapper::Nothing(_)), "{:?}", row[1]);
+}
+
+#[tokio::test]
+async fn a_null_optional_match_is_none_when_unpacked_with_optional() {
+    let builder = user_and_group(true);
+    let mut replay = without_group(&builder);
+    let mut rows = builder.run_with(&mut replay, optional(EntityWrapper::from_db_entity)).await.unwrap();
+    let row = rows.remove(0);
+    assert!(matches!(&row[0], Some(EntityWrapper::User(_))));
+    assert!(row[1].is_none());
+}
+
+#[tokio::test]
+async fn a_null_column_outside_an_optional_match_is_an_error() {
+    let builder = user_and_group(false);
+    let mut replay = without_group(&builder);
+    let result = builder.run_with(&mut replay, EntityWrapper::from_db_entity).await;
+    assert!(matches!(&result, Err(Neo4gError::Decode { reason, .. }) if reason.contains("group2")), "{:?}", result);
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use example_consumer::objects::{Component, ComponentProps, ComponentType, HasComponent, Page, PageProps};
+use neo4g::prepared_query::PreparedQuery;
+use neo4g::query_builder::{self, CompOper, CompareOperator, Neo4gBuilder, Neo4gError, Raw, Where};
+use neo4g_macro_rules::{no_props, props};
+use neo4rs::BoltType;
+
+fn page_components() -> (PreparedQuery, Page, Component) {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let mut has_component = HasComponent::default();
+    let mut component = Component::new("cid1", "path1", ComponentType::Type1, false);
+    let prepared = Neo4gBuilder::new()
+        .get()
+            .node(&mut page, props!(page => page.id)).add_to_return()
+            .relation(&mut has_component, no_props!())
+            .node(&mut component, props!(component => component.id))
+            .filter(Where::new()
+                .condition_prop(&page, Some(&page.id), CompareOperator::by_prop(CompOper::Eq, &page.id, query_builder::RefType::Val))
+            )
+        .end_statement()
+        .prepare();
+    (prepared, page, component)
+}
+
+fn param(prepared: &PreparedQuery, slot: &str) -> BoltType {
+    prepared.build().1.remove(slot).unwrap_or_else(|| panic!("no slot {}", slot))
+}
+
+fn condition_slot(prepared: &PreparedQuery) -> String {
+    prepared.slots().into_iter().find(|slot| slot.starts_with("co_id")).expect("condition slot").to_string()
+}
+
+#[test]
+fn bind_sets_the_entity_and_condition_slots_of_the_props_entity() {
+    let (mut prepared, _, _) = page_components();
+    prepared.bind(PageProps::Id("pid2".into())).unwrap();
+    let slot = condition_slot(&prepared);
+    assert_eq!(param(&prepared, "page1_id"), BoltType::from("pid2"));
+    assert_eq!(param(&prepared, &slot), BoltType::from("pid2"));
+}
+
+#[test]
+fn bind_leaves_slots_of_other_entities_with_the_same_prop_alone() {
+    let (mut prepared, _, _) = page_components();
+    prepared.bind(ComponentProps::Id("cid2".into())).unwrap();
+    let slot = condition_slot(&prepared);
+    assert_eq!(param(&prepared, "component2_id"), BoltType::from("cid2"));
+    assert_eq!(param(&prepared, "page1_id"), BoltType::from("pid1"));
+    assert_eq!(param(&prepared, &slot), BoltType::from("pid1"));
+}
+
+#[test]
+fn bind_fails_when_the_prop_matches_more_than_one_entity() {
+    let mut page1 = Page::new("pid1", "path1", Vec::new(), false);
+    let mut page2 = Page::new("pid2", "path2", Vec::new(), false);
+    let mut has_component = HasComponent::default();
+    let mut prepared = Neo4gBuilder::new()
+        .get()
+            .node(&mut page1, props!(page1 => page1.id)).add_to_return()
+            .relation(&mut has_component, no_props!())
+            .node(&mut page2, props!(page2 => page2.id)).add_to_return()
+        .end_statement()
+        .prepare();
+    match prepared.bind(PageProps::Id("pid3".into())) {
+        Err(Neo4gError::AmbiguousSlot { slot, aliases }) => {
+            assert_eq!(slot, "id");
+            assert_eq!(aliases, vec!["page1".to_string(), "page2".to_string()]);
+        },
+        other => panic!("expected AmbiguousSlot, got {:?}", other.map(|_| ())),
+    }
+    prepared.bind_for(&page2, PageProps::Id("pid3".into())).unwrap();
+    assert_eq!(param(&prepared, "page1_id"), BoltType::from("pid1"));
+    assert_eq!(param(&prepared, "page2_id"), BoltType::from("pid3"));
+}
+
+#[test]
+fn bind_sets_set_slots_of_the_props_entity() {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let mut prepared = Neo4gBuilder::new()
+        .get()
+            .node(&mut page, props!(page => page.id))
+            .set(&page, props!(page => PageProps::Path("/old".into())))
+        .end_statement()
+        .prepare();
+    let slot = prepared.slots().into_iter().find(|slot| slot.starts_with("set_path")).expect("set slot").to_string();
+    prepared.bind(PageProps::Path("/new".into())).unwrap();
+    assert_eq!(param(&prepared, &slot), BoltType::from("/new"));
+}
+
+#[test]
+fn bind_leaves_raw_params_alone_even_when_named_like_generated_slots() {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let raw_params = HashMap::from([("set_id1".to_string(), BoltType::from("pid0")), ("co_idbeef".to_string(), BoltType::from("pid0"))]);
+    let mut prepared = Neo4gBuilder::new()
+        .get()
+            .node(&mut page, props!(page => page.id)).add_to_return()
+            .filter(Where::new()
+                .raw(Raw::new("{page}.id <> $set_id1 AND {page}.id <> $co_idbeef").entity("page", &page), raw_params)
+            )
+        .end_statement()
+        .prepare();
+    prepared.bind(PageProps::Id("pid2".into())).unwrap();
+    assert_eq!(param(&prepared, "page1_id"), BoltType::from("pid2"));
+    assert_eq!(param(&prepared, "set_id1"), BoltType::from("pid0"));
+    assert_eq!(param(&prepared, "co_idbeef"), BoltType::from("pid0"));
+}
+
+#[test]
+fn bind_fails_when_no_slot_matches() {
+    let (mut prepared, _, _) = page_components();
+    assert!(matches!(prepared.bind(PageProps::Path("/".into())), Err(Neo4gError::UnknownSlot { .. })));
+}
+
+#[test]
+fn bind_param_fails_for_an_unknown_slot() {
+    let (mut prepared, _, _) = page_components();
+    assert!(matches!(prepared.bind_param("nope", 1), Err(Neo4gError::UnknownSlot { .. })));
+    prepared.bind_param("page1_id", "pid9").unwrap();
+    assert_eq!(param(&prepared, "page1_id"), BoltType::from("pid9"));
+}
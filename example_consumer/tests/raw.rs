@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use example_consumer::objects::{Group, MemberOf};
+use neo4g::query_builder::{Neo4gBuilder, Neo4gError, Raw, Where};
+use neo4g_macro_rules::{no_props, props};
+use neo4rs::BoltType;
+
+mod common;
+use common::user;
+
+fn search(value: &str) -> HashMap<String, BoltType> {
+    HashMap::from([("search".to_string(), BoltType::from(value))])
+}
+
+#[test]
+fn raw_resolves_placeholders_and_starts_on_a_new_line() {
+    let mut user = user();
+    let (query, params) = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name))
+        .end_statement()
+        .raw(Raw::new("WITH {user} WHERE {user}.name =~ $search").entity("user", &user), search("ad.*"))
+        .unwrap()
+        .build();
+    assert!(query.ends_with("= false\nWITH user1 WHERE user1.name =~ $search"), "{}", query);
+    assert_eq!(params["search"], BoltType::from("ad.*"));
+}
+
+#[test]
+fn statement_raw_starts_on_a_new_line() {
+    let mut user = user();
+    let statement = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .raw(Raw::new("USING INDEX {user}:User(name)").entity("user", &user), HashMap::new())
+            .unwrap();
+    let query = statement.to_cypher_pretty();
+    assert!(query.contains("{name: $user1_name})\nUSING INDEX user1:User(name)"), "{}", query);
+}
+
+#[test]
+fn raw_fails_for_an_entity_that_is_not_in_the_query() {
+    let user = user();
+    let result = Neo4gBuilder::new()
+        .raw(Raw::new("RETURN {user}").entity("user", &user), HashMap::new());
+    assert!(matches!(result, Err(Neo4gError::UnknownEntity { .. })));
+}
+
+#[test]
+fn raw_fails_for_a_param_that_is_already_in_use() {
+    let result = Neo4gBuilder::new()
+        .raw(Raw::new("WITH $search AS s"), search("a"))
+        .unwrap()
+        .raw(Raw::new("WITH s, $search AS t"), search("b"));
+    assert!(matches!(result, Err(Neo4gError::ParamCollision { param }) if param == "search"));
+}
+
+#[test]
+fn try_filter_fails_for_a_raw_condition_param_that_the_query_already_uses() {
+    let mut user = user();
+    let result = Neo4gBuilder::new()
+        .raw(Raw::new("WITH $search AS s"), search("a"))
+        .unwrap()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .try_filter(Where::new().raw(Raw::new("{user}.name =~ $search").entity("user", &user), search("b")));
+    assert!(matches!(result, Err(Neo4gError::ParamCollision { param }) if param == "search"));
+}
+
+#[test]
+fn try_filter_fails_for_raw_conditions_that_share_a_param() {
+    let mut user = user();
+    let mut member_of = MemberOf::new(false);
+    let mut group = Group::new("default_access", false);
+    let result = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .relation(&mut member_of, no_props!())
+            .node(&mut group, props!(group => group.name))
+            .try_filter(Where::new()
+                .raw(Raw::new("{user}.name =~ $search").entity("user", &user), search("a"))
+                .join(neo4g::query_builder::CompareJoiner::And)
+                .raw(Raw::new("{group}.name =~ $search").entity("group", &group), search("b"))
+            );
+    assert!(matches!(result, Err(Neo4gError::ParamCollision { param }) if param == "search"));
+}
+
+#[test]
+fn filter_resolves_raw_conditions() {
+    let mut user = user();
+    let query = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .filter(Where::new().raw(Raw::new("{user}.name =~ $search").entity("user", &user), search("ad.*")))
+        .end_statement()
+        .build()
+        .0;
+    assert!(query.contains("AND (user1.name =~ $search)"), "{}", query);
+}
+
+#[test]
+#[should_panic(expected = "search")]
+fn filter_panics_for_a_raw_condition_param_that_the_query_already_uses() {
+    let mut user = user();
+    Neo4gBuilder::new()
+        .raw(Raw::new("WITH $search AS s"), search("a"))
+        .unwrap()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .filter(Where::new().raw(Raw::new("{user}.name =~ $search").entity("user", &user), search("b")));
+}
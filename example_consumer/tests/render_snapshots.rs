@@ -0,0 +1,116 @@
+use example_consumer::objects::{hash_password, Component, ComponentType, Group, HasComponent, MemberOf, Page, PageProps};
+use neo4g::query_builder::Neo4gBuilder;
+use neo4g::render::{assert_cypher_snapshot, Rendered};
+use neo4g_macro_rules::{no_props, props};
+
+mod common;
+use common::user;
+
+fn get_user_groups() -> Rendered {
+    let mut user = user();
+    let mut group = Group::new("default_access", false);
+    let mut member_of = MemberOf::new(false);
+    Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name)).add_to_return()
+            .relation(&mut member_of, no_props!()).add_to_return()
+            .node(&mut group, no_props!()).add_to_return()
+        .end_statement()
+        .render()
+}
+
+fn merge_user() -> Rendered {
+    let mut user = user();
+    Neo4gBuilder::new()
+        .merge()
+            .node(&mut user, props!(user => user.name)).add_to_return()
+            .on_create()
+                .set(&user, props!(user => user.forename, user.surname))
+            .on_match()
+                .set(&user, props!(user => user.password))
+        .end_statement()
+        .render()
+}
+
+fn get_page_components() -> Rendered {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let mut has_component = HasComponent::default();
+    let mut component = Component::new("cid1", "path1", ComponentType::Type1, false);
+    Neo4gBuilder::new()
+        .get()
+            .node(&mut page, props!(page => page.id))
+            .relation(&mut has_component, no_props!())
+            .node(&mut component, no_props!()).add_to_return()
+        .end_statement()
+        .render()
+}
+
+#[test]
+fn get_user_groups_matches_snapshot() {
+    assert_cypher_snapshot("tests/snapshots/get_user_groups.cypher", &get_user_groups());
+}
+
+#[test]
+fn merge_user_matches_snapshot() {
+    assert_cypher_snapshot("tests/snapshots/merge_user.cypher", &merge_user());
+}
+
+#[test]
+fn get_page_components_matches_snapshot() {
+    assert_cypher_snapshot("tests/snapshots/get_page_components.cypher", &get_page_components());
+}
+
+#[test]
+fn render_is_stable_across_builds() {
+    let first = get_user_groups();
+    for _ in 0..50 {
+        assert_eq!(get_user_groups(), first);
+    }
+}
+
+#[test]
+fn return_lists_aliases_in_the_order_they_were_added() {
+    let rendered = get_user_groups();
+    assert!(rendered.query.ends_with("RETURN user1, member_of1, group2"), "{}", rendered.query);
+}
+
+#[test]
+fn additional_labels_placeholder_never_reaches_the_query() {
+    for rendered in [get_user_groups(), merge_user(), get_page_components()] {
+        assert!(!rendered.query.contains("AdditionalLabels"), "{}", rendered.query);
+    }
+}
+
+#[test]
+fn merge_statement_pretty_matches_the_ended_statement() {
+    let mut user = user();
+    let statement = Neo4gBuilder::new()
+        .merge()
+            .node(&mut user, props!(user => user.name)).add_to_return()
+            .on_create()
+                .set(&user, props!(user => user.forename))
+            .on_match()
+                .set(&user, props!(user => user.password));
+    let pretty = statement.to_cypher_pretty();
+    let script = statement.to_browser_script();
+    let ended = statement.end_statement();
+    assert!(pretty.contains("ON CREATE") && pretty.contains("ON MATCH"), "{}", pretty);
+    assert_eq!(pretty, ended.to_cypher_pretty().replace("\nRETURN user1", ""));
+    assert!(script.contains(&format!(":param set_password2 => '{}'", hash_password("hash"))), "{}", script);
+}
+
+#[test]
+fn match_statement_pretty_includes_sets_and_version_guards() {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let statement = Neo4gBuilder::new()
+        .get()
+            .node(&mut page, props!(page => page.id))
+            .set(&page, props!(page => PageProps::Path("/index".into())));
+    let pretty = statement.to_cypher_pretty();
+    let script = statement.to_browser_script();
+    let ended = statement.end_statement();
+    assert!(pretty.contains("WHERE page1.version = $expected_version_page1"), "{}", pretty);
+    assert!(pretty.contains("SET page1.path = $set_path1"), "{}", pretty);
+    assert_eq!(pretty, ended.to_cypher_pretty().replace("\nRETURN true AS version_matched", ""));
+    assert!(script.contains(":param expected_version_page1 => 0"), "{}", script);
+}
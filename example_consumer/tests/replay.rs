@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use example_consumer::entity_wrapper::EntityWrapper;
+use example_consumer::objects::User;
+use neo4g::query_builder::Neo4gBuilder;
+use neo4g::replay::{RecordingExecutor, ReplayExecutor};
+use neo4g::traits::{MatchedNode, WrappedNeo4gEntity};
+use neo4g_macro_rules::props;
+
+mod common;
+use common::{user, user_node};
+
+fn get_user() -> Neo4gBuilder<MatchedNode> {
+    let mut user = user();
+    Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name)).add_to_return()
+        .end_statement()
+}
+
+fn only_user(mut rows: Vec<Vec<EntityWrapper>>) -> User {
+    match rows.remove(0).remove(0) {
+        EntityWrapper::User(user) => user,
+        other => panic!("expected a user, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn recorded_entities_decode_the_same_when_replayed() {
+    let path = std::env::temp_dir().join(format!("neo4g_replay_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut database = ReplayExecutor::new();
+    database.answer(&get_user().render().query, vec![HashMap::from([("user1".to_string(), user_node())])]);
+    let mut recorder = RecordingExecutor::new(database, &path);
+    let recorded = only_user(get_user().run_with(&mut recorder, EntityWrapper::from_db_entity).await.unwrap());
+    let mut replay = ReplayExecutor::from_file(&path).unwrap();
+    let replayed = only_user(get_user().run_with(&mut replay, EntityWrapper::from_db_entity).await.unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(*replayed.name(), "admin");
+    assert_eq!(replayed.name(), recorded.name());
+    assert_eq!(replayed.forename(), recorded.forename());
+    assert_eq!(replayed.updated(), recorded.updated());
+    assert_eq!(*replayed.updated(), NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_milli_opt(9, 30, 15, 125).unwrap());
+}
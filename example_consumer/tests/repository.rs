@@ -0,0 +1,56 @@
+use example_consumer::objects::{Page, PageProps, User, UserProps};
+use neo4g::query_builder::Neo4gError;
+use neo4g::repository::{Neo4gRepository, Paging};
+use neo4rs::BoltType;
+
+mod common;
+use common::NoRows;
+
+#[tokio::test]
+async fn update_matches_on_the_key_left_by_the_before_save_hook() {
+    let user = User::new("8F8C54B6-5D22-45D6-9A24-DFACAA8D37F5", "admin", "hash", "system", "user", Vec::new(), false);
+    let mut executor = NoRows::default();
+    let updated = user.update_with(&mut executor, &[UserProps::CurrentSurname]).await.unwrap();
+    assert!(updated.is_none());
+    let (query, params) = &executor.sent[0];
+    assert!(query.starts_with("MATCH (n:User {id: $n_id})"), "{}", query);
+    assert_eq!(params["n_id"], BoltType::from("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5"));
+}
+
+#[tokio::test]
+async fn update_sets_the_values_left_by_the_before_save_hook() {
+    let page = Page::new("pid1", "/home/", Vec::new(), false);
+    let mut executor = NoRows::default();
+    let result = page.update_with(&mut executor, &[PageProps::CurrentPath]).await;
+    assert!(matches!(result, Err(Neo4gError::ConcurrentModification { .. })));
+    let (query, params) = &executor.sent[0];
+    assert!(query.contains("SET n.path = $update_path"), "{}", query);
+    assert_eq!(params["update_path"], BoltType::from("/home"));
+}
+
+#[tokio::test]
+async fn find_by_orders_by_the_key_so_the_first_match_is_stable() {
+    let mut executor = NoRows::default();
+    let found = User::find_by_with(&mut executor, UserProps::Name("admin".into())).await.unwrap();
+    assert!(found.is_none());
+    let (query, params) = &executor.sent[0];
+    assert!(query.starts_with("MATCH (n:User {name: $n_name})"), "{}", query);
+    assert!(query.ends_with("RETURN n\nORDER BY n.id\nLIMIT 1"), "{}", query);
+    assert_eq!(params["n_name"], BoltType::from("admin"));
+}
+
+#[tokio::test]
+async fn repository_methods_run_against_any_executor() {
+    let user = User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "hash", "system", "user", Vec::new(), false);
+    let mut executor = NoRows::default();
+    assert_eq!(User::count_with(&mut executor, &[]).await.unwrap(), 0);
+    assert!(User::find_all_with(&mut executor, &[], Paging::page(1, 20)).await.unwrap().is_empty());
+    let saved = user.save_with(&mut executor).await.unwrap();
+    assert_eq!(*saved.name(), "admin");
+    user.delete_with(&mut executor, true).await.unwrap();
+    let queries = executor.queries();
+    assert!(queries[0].ends_with("RETURN count(n) AS count"), "{}", queries[0]);
+    assert!(queries[1].ends_with("SKIP $page_skip\nLIMIT $page_limit"), "{}", queries[1]);
+    assert!(queries[2].starts_with("MERGE (n:User {id: $n_id})"), "{}", queries[2]);
+    assert!(queries[3].starts_with("MATCH (n:User {id: $n_id})\nSET n.deleted = true"), "{}", queries[3]);
+}
@@ -0,0 +1,13 @@
+use example_consumer::objects::{Component, Group, Page, User};
+
+#[test]
+fn key_fields_are_made_unique() {
+    assert_eq!(User::schema().statements(), vec!["CREATE CONSTRAINT user_id_unique IF NOT EXISTS FOR (n:User) REQUIRE n.id IS UNIQUE".to_string()]);
+    assert_eq!(Group::schema().statements(), vec!["CREATE CONSTRAINT group_id_unique IF NOT EXISTS FOR (n:Group) REQUIRE n.id IS UNIQUE".to_string()]);
+}
+
+#[test]
+fn unique_fields_are_made_unique() {
+    assert_eq!(Page::schema().statements(), vec!["CREATE CONSTRAINT page_id_unique IF NOT EXISTS FOR (n:Page) REQUIRE n.id IS UNIQUE".to_string()]);
+    assert_eq!(Component::schema().label(), "Component");
+}
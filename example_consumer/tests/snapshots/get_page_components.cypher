@@ -0,0 +1,4 @@
+:param page1_id => 'pid1'
+MATCH (page1:Page {id: $page1_id})-[has_component1:HAS_COMPONENT]->(component2:Component)
+  WHERE coalesce(page1.deleted, false) = false AND coalesce(has_component1.deleted, false) = false AND coalesce(component2.deleted, false) = false
+RETURN component2;
@@ -0,0 +1,4 @@
+:param user1_name => 'admin'
+MATCH (user1:User {name: $user1_name})-[member_of1:MEMBER_OF]->(group2:Group)
+  WHERE coalesce(user1.deleted, false) = false AND coalesce(member_of1.deleted, false) = false AND coalesce(group2.deleted, false) = false
+RETURN user1, member_of1, group2;
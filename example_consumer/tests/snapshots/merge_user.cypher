@@ -0,0 +1,10 @@
+:param set_forename1 => 'system'
+:param set_password2 => '68c5a57aaee15d23'
+:param set_surname1 => 'user'
+:param user1_name => 'admin'
+MERGE (user1:User {name: $user1_name})
+  ON MATCH
+  SET user1.updated = localdatetime(), user1.password = $set_password2
+  ON CREATE
+  SET user1.created = localdatetime(), user1.updated = localdatetime(), user1.forename = $set_forename1, user1.surname = $set_surname1
+RETURN user1;
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use example_consumer::objects::{Group, MemberOf, Page, PageProps};
+use neo4g::query_builder::{self, CompOper, CompareJoiner, CompareOperator, Neo4gBuilder, Raw, Where};
+use neo4g_macro_rules::{no_props, props};
+
+mod common;
+use common::user;
+
+#[test]
+fn soft_deleted_entities_are_filtered_with_a_null_safe_condition() {
+    let mut user = user();
+    let query = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name)).add_to_return()
+        .end_statement()
+        .render()
+        .query;
+    assert!(query.starts_with("MATCH (user1:User {name: $user1_name})\n"), "{}", query);
+    assert!(query.contains("WHERE coalesce(user1.deleted, false) = false"), "{}", query);
+}
+
+#[test]
+fn with_deleted_applies_to_entities_added_before_it() {
+    let mut user = user();
+    let mut member_of = MemberOf::new(false);
+    let mut group = Group::new("default_access", false);
+    let query = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name)).add_to_return()
+            .relation(&mut member_of, no_props!())
+            .node(&mut group, no_props!())
+            .with_deleted()
+        .end_statement()
+        .render()
+        .query;
+    assert!(!query.contains("deleted"), "{}", query);
+}
+
+#[test]
+fn only_deleted_applies_to_every_entity_in_the_statement() {
+    let mut user = user();
+    let mut member_of = MemberOf::new(false);
+    let mut group = Group::new("default_access", false);
+    let query = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .only_deleted()
+            .relation(&mut member_of, no_props!())
+            .node(&mut group, no_props!()).add_to_return()
+        .end_statement()
+        .render()
+        .query;
+    assert!(query.contains("WHERE user1.deleted = true AND member_of1.deleted = true AND group2.deleted = true"), "{}", query);
+}
+
+#[test]
+fn filter_conditions_are_grouped_after_the_soft_delete_conditions() {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let query = Neo4gBuilder::new()
+        .get()
+            .node(&mut page, no_props!()).add_to_return()
+            .filter(Where::new()
+                .condition_prop(&page, Some(&page.id), CompareOperator::by_prop(CompOper::Eq, &page.id, query_builder::RefType::Val))
+            )
+        .end_statement()
+        .render()
+        .query;
+    assert!(query.contains("WHERE coalesce(page1.deleted, false) = false AND (page1.id = $"), "{}", query);
+    assert!(query.contains(")\nRETURN page1"), "{}", query);
+}
+
+#[test]
+fn filters_after_other_clauses_are_grouped_with_the_first() {
+    let mut page = Page::new("pid1", "path1", Vec::new(), false);
+    let query = Neo4gBuilder::new()
+        .get()
+            .node(&mut page, no_props!()).add_to_return()
+            .filter(Where::new()
+                .condition_prop(&page, Some(&page.id), CompareOperator::by_prop(CompOper::Eq, &page.id, query_builder::RefType::Val))
+                .join(CompareJoiner::Or)
+                .condition_prop(&page, Some(&page.path), CompareOperator::by_prop(CompOper::Eq, &page.path, query_builder::RefType::Val))
+            )
+            .raw(Raw::new("CALL db.awaitIndexes()"), HashMap::new()).unwrap()
+            .filter(Where::new()
+                .condition_prop(&page, Some(&page.path), CompareOperator::by_prop(CompOper::Eq, &page.path, query_builder::RefType::Val))
+            )
+        .end_statement()
+        .build()
+        .0;
+    let conditions = query.lines().find(|line| line.starts_with("WHERE ")).unwrap();
+    assert!(conditions.starts_with("WHERE coalesce(page1.deleted, false) = false AND ((page1.id = $"), "{}", query);
+    assert!(conditions.contains(" OR page1.path = $") && conditions.contains(") AND (page1.path = $"), "{}", query);
+    assert!(conditions.ends_with("))"), "{}", query);
+    assert!(query.contains("))\nCALL db.awaitIndexes()"), "{}", query);
+}
+
+#[test]
+fn an_explicit_soft_delete_prop_replaces_the_filter() {
+    let mut page = Page::new("pid1", "path1", Vec::new(), true);
+    let query = Neo4gBuilder::new()
+        .get()
+            .node(&mut page, props!(page => PageProps::Deleted(true))).add_to_return()
+        .end_statement()
+        .render()
+        .query;
+    assert!(query.contains("{deleted: $page1_deleted}"), "{}", query);
+    assert!(!query.contains("coalesce"), "{}", query);
+}
@@ -0,0 +1,37 @@
+use example_consumer::objects::{Group, MemberOf};
+use neo4g::prelude::{BoltType, SyncOptions};
+use neo4g::query_builder::Neo4gBuilder;
+use neo4g_macro_rules::prop;
+
+mod common;
+use common::user;
+
+#[test]
+fn soft_delete_relations_are_soft_deleted_and_restored() {
+    let user = user();
+    let member_of = MemberOf::new(false);
+    let groups = vec![Group::new("admins", false), Group::new("users", false)];
+    let (query, params) = Neo4gBuilder::new()
+        .sync_relations(&user, prop!(user.id), &member_of, &groups, prop!(group.id), SyncOptions::default())
+        .build();
+    assert!(query.starts_with("MATCH (from:User {id: $sync_from_key})\nCALL {\nWITH from\nUNWIND $sync_to_keys AS key\nMATCH (to:Group {id: key})"), "{}", query);
+    assert!(query.contains("WHERE existing IS NULL OR existing.deleted = true"), "{}", query);
+    assert!(query.contains("SET rel.deleted = false, rel.updated = localdatetime()"), "{}", query);
+    assert!(query.contains("CALL {\nWITH from\nMATCH (from)-[rel:MEMBER_OF]->(to:Group)"), "{}", query);
+    assert!(query.contains("AND coalesce(rel.deleted, false) = false\nSET rel.deleted = true, rel.updated = localdatetime()"), "{}", query);
+    assert!(!query.contains("DELETE rel") && !query.contains("CALL (from)"), "{}", query);
+    match params.get("sync_to_keys") {
+        Some(BoltType::List(keys)) => assert_eq!(keys.len(), 2),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn options_choose_the_soft_delete_property() {
+    let user = user();
+    let member_of = MemberOf::new(false);
+    let (query, _) = Neo4gBuilder::new()
+        .sync_relations(&user, prop!(user.id), &member_of, &Vec::<Group>::new(), prop!(group.id), SyncOptions::default().soft_delete("archived"))
+        .build();
+    assert!(query.contains("WHERE NOT to.id IN $sync_to_keys AND coalesce(rel.archived, false) = false\nSET rel.archived = true"), "{}", query);
+}
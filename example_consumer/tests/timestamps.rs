@@ -0,0 +1,64 @@
+use example_consumer::objects::{Group, MemberOf};
+use neo4g::query_builder::Neo4gBuilder;
+use neo4g_macro_rules::props;
+
+mod common;
+use common::user;
+
+#[test]
+fn created_nodes_set_timestamps_on_the_server() {
+    let mut user = user();
+    let (query, params) = Neo4gBuilder::new()
+        .create()
+            .node(&mut user)
+        .end_statement()
+        .build();
+    assert!(query.contains("created: localdatetime(), updated: localdatetime()"), "{}", query);
+    assert!(!params.contains_key("user1_created"), "{:?}", params.keys());
+    assert!(!params.contains_key("user1_updated"), "{:?}", params.keys());
+    assert!(params.contains_key("user1_name"), "{:?}", params.keys());
+}
+
+#[test]
+fn created_relations_set_timestamps_on_the_server() {
+    let mut user = user();
+    let mut member_of = MemberOf::new(false);
+    let mut group = Group::new("admins", false);
+    let (query, params) = Neo4gBuilder::new()
+        .create()
+            .node(&mut user)
+            .relation(&mut member_of)
+            .node(&mut group)
+        .end_statement()
+        .build();
+    assert!(query.contains("[member_of1:MEMBER_OF {created: localdatetime(), updated: localdatetime()"), "{}", query);
+    assert!(!params.keys().any(|key| key.starts_with("member_of1_created") || key.starts_with("member_of1_updated")), "{:?}", params.keys());
+}
+
+#[test]
+fn merged_nodes_set_timestamps_under_one_header_per_action() {
+    let mut user = user();
+    let (query, _) = Neo4gBuilder::new()
+        .merge()
+            .node(&mut user, props!(user => user.name))
+            .on_create().set(&user, props!(user => user.forename))
+            .on_match().set(&user, props!(user => user.password))
+        .end_statement()
+        .build();
+    assert_eq!(query.matches("ON CREATE").count(), 1, "{}", query);
+    assert_eq!(query.matches("ON MATCH").count(), 1, "{}", query);
+    assert!(query.contains("ON CREATE\nSET user1.created = localdatetime(), user1.updated = localdatetime(), user1.forename = $set_forename1"), "{}", query);
+    assert!(query.contains("ON MATCH\nSET user1.updated = localdatetime(), user1.password = $set_password2"), "{}", query);
+}
+
+#[test]
+fn merged_nodes_without_sets_still_get_timestamps() {
+    let mut group = Group::new("admins", false);
+    let (query, _) = Neo4gBuilder::new()
+        .merge()
+            .node(&mut group, props!(group => group.name))
+        .end_statement()
+        .build();
+    assert!(query.contains("ON CREATE\nSET group1.created = localdatetime(), group1.updated = localdatetime()"), "{}", query);
+    assert!(query.contains("ON MATCH\nSET group1.updated = localdatetime()"), "{}", query);
+}
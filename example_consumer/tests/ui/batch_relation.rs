@@ -0,0 +1,9 @@
+use example_consumer::objects::MemberOf;
+use neo4g::prelude::*;
+use neo4g_macro_rules::prop;
+
+fn main() {
+    let member_ofs = vec![MemberOf::new(false)];
+    let _ = Neo4gBuilder::new().create_many(&member_ofs);
+    let _ = Neo4gBuilder::new().merge_many(&member_ofs, prop!(member_of.created));
+}
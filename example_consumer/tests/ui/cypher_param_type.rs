@@ -0,0 +1,7 @@
+use example_consumer::objects::User;
+use neo4g_derive::cypher;
+
+fn main() {
+    let name = 42;
+    let _ = cypher!(MATCH (u: User {name: $name}) RETURN u);
+}
@@ -0,0 +1,5 @@
+use neo4g_derive::cypher;
+
+fn main() {
+    let _ = cypher!(MATCH (u: Customer) RETURN u);
+}
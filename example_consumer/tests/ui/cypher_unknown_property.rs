@@ -0,0 +1,6 @@
+use example_consumer::objects::User;
+use neo4g_derive::cypher;
+
+fn main() {
+    let _ = cypher!(MATCH (u: User) WHERE u.nickname = "admin" RETURN u);
+}
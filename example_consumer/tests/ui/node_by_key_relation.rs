@@ -0,0 +1,9 @@
+use example_consumer::objects::MemberOf;
+use neo4g::prelude::*;
+
+fn main() {
+    let mut member_of = MemberOf::new(false);
+    let _ = Neo4gBuilder::new()
+        .get()
+            .node_by_key(&mut member_of);
+}
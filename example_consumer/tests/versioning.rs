@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use example_consumer::entity_wrapper::EntityWrapper;
+use example_consumer::objects::{Page, PageProps, User};
+use neo4g::query_builder::{Neo4gBuilder, Neo4gError};
+use neo4g::replay::ReplayExecutor;
+use neo4g::traits::WrappedNeo4gEntity;
+use neo4g_macro_rules::{prop, props};
+use neo4rs::{BoltInteger, BoltList, BoltMap, BoltNode, BoltString, BoltType};
+
+mod common;
+use common::NoRows;
+
+fn set_page_path() -> Neo4gBuilder<neo4g::traits::MatchedNode> {
+    let mut page = Page::new("pid1", "/home", Vec::new(), false);
+    Neo4gBuilder::new()
+        .get()
+            .node(&mut page, props!(page => page.id))
+            .set(&page, props!(page => PageProps::Path("/index".into())))
+        .end_statement()
+}
+
+fn set_and_return_page(page: &mut Page, path: &str) -> Neo4gBuilder<neo4g::traits::MatchedNode> {
+    Neo4gBuilder::new()
+        .get()
+            .node(&mut *page, props!(page => page.id)).add_to_return()
+            .set(&*page, props!(page => PageProps::Path(path.into())))
+        .end_statement()
+}
+
+fn page_node(path: &str, version: i64) -> BoltType {
+    let mut properties = BoltMap::new();
+    properties.put(BoltString::new("id"), "pid1".into());
+    properties.put(BoltString::new("path"), path.into());
+    properties.put(BoltString::new("version"), version.into());
+    let mut labels = BoltList::new();
+    labels.push("Page".into());
+    BoltType::Node(BoltNode { id: BoltInteger::new(1), labels, properties })
+}
+
+#[tokio::test]
+async fn versioned_set_without_rows_is_a_concurrent_modification() {
+    let result = set_page_path().run_with(&mut NoRows::default(), EntityWrapper::from_db_entity).await;
+    assert!(matches!(result, Err(Neo4gError::ConcurrentModification { .. })));
+}
+
+#[tokio::test]
+async fn prepared_versioned_set_without_rows_is_a_concurrent_modification() {
+    let prepared = set_page_path().prepare();
+    let result = prepared.run_with(&mut NoRows::default(), EntityWrapper::from_db_entity).await;
+    assert!(matches!(result, Err(Neo4gError::ConcurrentModification { .. })));
+}
+
+#[tokio::test]
+async fn unversioned_set_without_rows_is_not_an_error() {
+    let mut user = User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "hash", "system", "user", Vec::new(), false);
+    let prepared = Neo4gBuilder::new()
+        .get()
+            .node(&mut user, props!(user => user.name))
+            .set(&user, props!(user => user.surname))
+        .end_statement()
+        .prepare();
+    let result = prepared.run_with(&mut NoRows::default(), EntityWrapper::from_db_entity).await;
+    assert!(matches!(result, Ok(rows) if rows.is_empty()));
+}
+
+#[tokio::test]
+async fn versioned_merge_many_guards_and_checks_every_chunk() {
+    let pages = vec![Page::new("pid1", "/a", Vec::new(), false), Page::new("pid2", "/b", Vec::new(), false)];
+    let batch = Neo4gBuilder::new().merge_many(&pages, prop!(page.id));
+    let (query, _) = batch.build().remove(0);
+    assert!(query.contains("WHERE n.version = row.version"), "{}", query);
+    assert!(query.contains("n.version = row.version + 1"), "{}", query);
+    let mut executor = NoRows::default();
+    let result = batch.run_with(&mut executor, EntityWrapper::from_db_entity).await;
+    assert!(matches!(result, Err(Neo4gError::ConcurrentModification { .. })));
+    assert_eq!(executor.sent.len(), 1);
+}
+
+#[tokio::test]
+async fn unversioned_create_many_is_not_checked() {
+    let users = vec![User::new("8f8c54b6-5d22-45d6-9a24-dfacaa8d37f5", "admin", "hash", "system", "user", Vec::new(), false)];
+    let result = Neo4gBuilder::new().create_many(&users).run_with(&mut NoRows::default(), EntityWrapper::from_db_entity).await;
+    assert!(matches!(result, Ok(rows) if rows.is_empty()));
+}
+
+#[tokio::test]
+async fn a_second_versioned_set_needs_the_returned_entity() {
+    let mut page = Page::new("pid1", "/home", Vec::new(), false);
+    let (query, _) = set_and_return_page(&mut page.clone(), "/index").build();
+    let mut replay = ReplayExecutor::new();
+    // build leaves out the RETURN clause that running the query adds.
+    replay.answer(&format!("{}\nRETURN page1", query), vec![HashMap::from([("page1".to_string(), page_node("/index", 1))])]);
+    let mut returned = set_and_return_page(&mut page, "/index").run_with(&mut replay, EntityWrapper::from_db_entity).await.unwrap();
+    let EntityWrapper::Page(mut saved) = returned.remove(0).remove(0) else { panic!("expected a page") };
+    // The entity passed in still holds the version it was read at, so setting it again would be a concurrent modification.
+    let (_, stale) = set_and_return_page(&mut page, "/start").build();
+    assert_eq!(stale["expected_version_page1"], BoltType::from(0));
+    assert_eq!(*saved.version(), 1);
+    let (_, params) = set_and_return_page(&mut saved, "/start").build();
+    assert_eq!(params["expected_version_page1"], BoltType::from(1));
+}
@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+
+use neo4rs::{BoltList, BoltMap, BoltString, BoltType, Graph, Txn};
+
+use crate::executor::{execute, Executor};
+use crate::query_builder::{DbEntityWrapper, EntityType, Neo4gBuilder, Neo4gError, Result};
+use crate::render::pretty_cypher;
+use crate::timestamps;
+use crate::traits::{Empty, NodeEntity, QueryParam};
+
+/// The number of entities written per query when no batch size is set.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// A bulk write of many nodes, sent as a list-of-maps param and unwound into one CREATE or MERGE per row.
+/// Rows are split into chunks of batch_size, and each chunk is sent as its own query.
+#[derive(Debug, Clone)]
+pub struct Neo4gBatch {
+    label: String,
+    key: Option<&'static str>,
+    version: Option<&'static str>,
+    rows: Vec<BoltType>,
+    created_sets: Vec<String>,
+    updated_sets: Vec<String>,
+    batch_size: usize,
+    returning: bool,
+}
+
+impl Neo4gBuilder<Empty> {
+    /// Creates one node per entity, using every non-excluded property of each entity. Empty generated ids are filled in.
+    /// # Example
+    /// ```rust
+    /// Neo4gBuilder::new()
+    ///     .create_many(&users)
+    ///     .batch_size(5000)
+    ///     .run_query(graph, EntityWrapper::from_db_entity).await?;
+    /// ```
+    /// The example above generates the following query for each chunk of 5000 users:
+    /// ```rust
+    /// UNWIND $rows AS row
+    /// CREATE (n:User)
+    /// SET n = row
+    /// ```
+    /// NOTE: Only nodes can be written in bulk, as relations need their start and end nodes matched, so T has to be a NodeEntity.
+    pub fn create_many<T: NodeEntity + Default>(self, entities: &[T]) -> Neo4gBatch {
+        Neo4gBatch::new(entities, None)
+    }
+    /// Merges one node per entity on the given key property, then sets every non-excluded property of each entity.
+    /// # Example
+    /// ```rust
+    /// Neo4gBuilder::new()
+    ///     .merge_many(&groups, prop!(group.id))
+    ///     .run_query(graph, EntityWrapper::from_db_entity).await?;
+    /// ```
+    /// The example above generates the following query for each chunk of groups:
+    /// ```rust
+    /// UNWIND $rows AS row
+    /// MERGE (n:Group {id: row.id})
+    /// SET n += row
+    /// ```
+    /// NOTE: The key is read from a default T, as are the label and managed fields, so an empty batch still renders a valid query.
+    /// Properties missing from the entities are left as they are on matched nodes.
+    /// Fields marked #[neo4g(created_at)] or #[neo4g(updated_at)] are left out of the rows and set to the database time instead.
+    /// Nodes with a field marked #[neo4g(version)] are only set if their version still matches the entity's, and it is incremented.
+    /// If any node in a chunk no longer matches, running the batch returns Neo4gError::ConcurrentModification,
+    /// and run_query rolls back every chunk. See Neo4gBatch::run_with for other executors.
+    pub fn merge_many<T, F>(self, entities: &[T], key: F) -> Neo4gBatch
+    where T: NodeEntity + Default, F: FnOnce(&T) -> T::Props {
+        let key = key(&T::default()).to_query_param().0;
+        Neo4gBatch::new(entities, Some(key))
+    }
+}
+
+impl Neo4gBatch {
+    fn new<T: NodeEntity + Default>(entities: &[T], key: Option<&'static str>) -> Self {
+        let template = T::default();
+        let updated_sets = timestamps::on_write_sets(&template, "n");
+        let created_sets = timestamps::on_create_sets(&template, "n").into_iter().filter(|set| !updated_sets.contains(set)).collect();
+        // Created nodes are new, so only merged ones are checked against their version.
+        let version = key.and(template.version_prop().map(|prop| prop.to_query_param().0));
+        let rows = entities.iter().map(|entity| {
+            let mut entity = entity.clone();
+            entity.ensure_id();
+            entity.hook_before_save();
+            let mut row = BoltMap::new();
+            for prop in entity.get_props() {
+                let (name, value) = prop.to_query_param();
+                if !timestamps::is_managed(&entity, name) {
+                    row.put(BoltString::from(name), value);
+                }
+            }
+            BoltType::Map(row)
+        }).collect();
+        Self {
+            label: template.get_label(),
+            key,
+            version,
+            rows,
+            created_sets,
+            updated_sets,
+            batch_size: DEFAULT_BATCH_SIZE,
+            returning: false,
+        }
+    }
+    /// Sets how many entities are written per query. Defaults to DEFAULT_BATCH_SIZE, and a size of 0 is treated as 1.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+    /// Returns the written nodes, so they can be unpacked by run_query.
+    pub fn add_to_return(mut self) -> Self {
+        self.returning = true;
+        self
+    }
+    /// Returns the query and params for each chunk, in the order they will be sent.
+    pub fn build(&self) -> Vec<(String, HashMap<String, BoltType>)> {
+        let query = self.query();
+        self.rows.chunks(self.batch_size).map(|chunk| {
+            let mut params = HashMap::new();
+            params.insert("rows".to_string(), BoltType::List(BoltList::from(chunk.to_vec())));
+            (query.clone(), params)
+        }).collect()
+    }
+    /// Returns the query sent for each chunk, indented with one clause per line.
+    pub fn to_cypher_pretty(&self) -> String {
+        pretty_cypher(&self.query())
+    }
+    /// Writes every chunk against a provided Graph. Each chunk is committed on its own, so a failure leaves earlier chunks written.
+    /// Versioned merges are the exception: they run in one transaction that is rolled back on a conflict, so nothing is written.
+    /// Returns the written nodes of every chunk as rows when add_to_return was called, and no rows otherwise.
+    /// # Example:
+    /// ```rust
+    /// .run_query(graph, EntityWrapper::from_db_entity).await;
+    /// ```
+    pub async fn run_query<F, R>(self, mut graph: Graph, unpack: F) -> Result<Vec<Vec<R>>>
+    where F: Fn(DbEntityWrapper) -> R {
+        if self.version.is_none() {
+            return self.run_with(&mut graph, unpack).await;
+        }
+        let mut txn = graph.start_txn().await?;
+        match self.run_with(&mut txn, unpack).await {
+            Ok(rows) => {
+                txn.commit().await?;
+                Ok(rows)
+            },
+            Err(e) => {
+                // The conflict is the error worth reporting, so a failed rollback is only logged.
+                if let Err(rollback_error) = txn.rollback().await {
+                    tracing::warn!(error = %rollback_error, "failed to roll back versioned batch");
+                }
+                Err(e)
+            },
+        }
+    }
+    /// Writes every chunk within a provided Txn, so the whole batch commits or rolls back together.
+    /// The Txn is left to the caller, who should roll it back if a versioned merge returns Neo4gError::ConcurrentModification.
+    /// # Example:
+    /// ```rust
+    /// .execute_in_txn(txn, EntityWrapper::from_db_entity).await;
+    /// ```
+    pub async fn execute_in_txn<F, R>(self, txn: &mut Txn, unpack: F) -> Result<Vec<Vec<R>>>
+    where F: Fn(DbEntityWrapper) -> R {
+        self.run_with(txn, unpack).await
+    }
+    /// Writes every chunk against any Executor, stopping at the first chunk that fails.
+    /// NOTE: A versioned merge writes the nodes of a chunk whose version matched even when others didn't,
+    /// so pass a Txn and roll it back on Neo4gError::ConcurrentModification to leave the batch unwritten.
+    /// # Example:
+    /// ```rust
+    /// .run_with(&mut recording_executor, EntityWrapper::from_db_entity).await;
+    /// ```
+    pub async fn run_with<E, F, R>(self, executor: &mut E, unpack: F) -> Result<Vec<Vec<R>>>
+    where E: Executor, F: Fn(DbEntityWrapper) -> R {
+        let return_refs = if self.returning {
+            vec![("n".to_string(), EntityType::Node)]
+        } else {
+            Vec::new()
+        };
+        let optional_refs = HashSet::new();
+        let mut result = Vec::new();
+        for (chunk, (query, params)) in self.rows.chunks(self.batch_size).zip(self.build()) {
+            let rows = execute(executor, query.clone(), params, &return_refs, &optional_refs, &unpack).await?;
+            // Versioned merges return a row per node whose version matched.
+            if self.version.is_some() && rows.len() < chunk.len() {
+                return Err(Neo4gError::ConcurrentModification { query });
+            }
+            if self.returning {
+                result.extend(rows);
+            }
+        }
+        Ok(result)
+    }
+    fn query(&self) -> String {
+        let mut query = String::from("UNWIND $rows AS row\n");
+        match self.key {
+            Some(key) => {
+                query.push_str(&format!("MERGE (n:{} {{{}: row.{}}})", self.label, key, key));
+                let mut created_sets = self.created_sets.clone();
+                if let Some(version) = self.version {
+                    // A new node starts at the version being saved, so it passes the guard like an unchanged existing node.
+                    created_sets.push(format!("n.{} = row.{}", version, version));
+                }
+                if !created_sets.is_empty() {
+                    query.push_str(&format!("\nON CREATE SET {}", created_sets.join(", ")));
+                }
+                match self.version {
+                    Some(version) => query.push_str(&format!("\nWITH n, row\nWHERE n.{} = row.{}\nSET n += row, n.{} = row.{} + 1", version, version, version, version)),
+                    None => query.push_str("\nSET n += row"),
+                }
+            },
+            None => {
+                query.push_str(&format!("CREATE (n:{})\nSET n = row", self.label));
+                for set in &self.created_sets {
+                    query.push_str(&format!(", {}", set));
+                }
+            },
+        }
+        for set in &self.updated_sets {
+            query.push_str(&format!(", {}", set));
+        }
+        if self.returning {
+            query.push_str("\nRETURN n");
+        } else if self.version.is_some() {
+            query.push_str("\nRETURN true AS version_matched");
+        }
+        query
+    }
+}
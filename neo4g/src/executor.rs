@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::time::Instant;
+
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt, TryStreamExt};
+use neo4rs::{BoltType, Graph, Query, Row, Txn};
+use tracing::{Instrument, Span};
+
+use crate::interceptor::{run_after, run_before, QueryContext, QueryOutcome};
+use crate::query_builder::{decode_row, DbEntityWrapper, EntityType, Neo4gError, Result};
+use crate::telemetry::{query_span, record_outcome};
+
+/// Something that can run a query and hand back its rows.
+/// Implemented for Graph and Txn, and can be implemented for fakes and recorders in tests.
+/// # Example:
+/// ```rust
+/// struct NoRows;
+/// impl Executor for NoRows {
+///     async fn fetch_rows(&mut self, _query: &str, _params: &HashMap<String, BoltType>) -> Result<Vec<Row>> {
+///         Ok(Vec::new())
+///     }
+/// }
+/// let result = builder.run_with(&mut NoRows, EntityWrapper::from_db_entity).await?;
+/// ```
+pub trait Executor: Send {
+    fn fetch_rows(&mut self, query: &str, params: &HashMap<String, BoltType>) -> impl Future<Output = Result<Vec<Row>>> + Send;
+}
+
+impl Executor for Graph {
+    async fn fetch_rows(&mut self, query: &str, params: &HashMap<String, BoltType>) -> Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        let mut result = self.execute(Query::new(query.to_string()).params(params.clone())).await?;
+        while let Some(row) = result.next().await? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+impl Executor for Txn {
+    async fn fetch_rows(&mut self, query: &str, params: &HashMap<String, BoltType>) -> Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        let mut result = self.execute(Query::new(query.to_string()).params(params.clone())).await?;
+        while let Some(row) = result.next(self.handle()).await? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+/// The single execution path shared by builders and prepared queries.
+/// Runs the interceptors and tracing around the executor, then decodes each row into the registered return objects.
+pub(crate) async fn execute<E, F, R>(executor: &mut E, query: String, params: HashMap<String, BoltType>, return_refs: &[(String, EntityType)], optional_refs: &HashSet<String>, unpack: F) -> Result<Vec<Vec<R>>>
+where E: Executor, F: Fn(DbEntityWrapper) -> R {
+    execute_rows(executor, query, params, |row| decode_row(row, return_refs, optional_refs, &unpack)).await
+}
+
+/// Same as execute, for queries that return values rather than entities. Each row is handed to decode as it is.
+pub(crate) async fn execute_rows<E, D, T>(executor: &mut E, query: String, params: HashMap<String, BoltType>, decode: D) -> Result<Vec<T>>
+where E: Executor, D: Fn(&Row) -> Result<T> {
+    let mut context = QueryContext { query, params };
+    run_before(&mut context)?;
+    let span = query_span(&context.query, &context.params);
+    let start = Instant::now();
+    let result = async {
+        let rows = executor.fetch_rows(&context.query, &context.params).await?;
+        rows.iter().map(&decode).collect::<Result<Vec<T>>>()
+    }.instrument(span.clone()).await
+        .map_err(|e| e.with_query(&context.query, &context.params));
+    let outcome = match &result {
+        Ok(rows) => QueryOutcome::Rows(rows.len()),
+        Err(e) => QueryOutcome::Error(e),
+    };
+    record_outcome(&span, start, &outcome);
+    run_after(&context, &outcome, start.elapsed());
+    result
+}
+
+/// Runs a query against a Graph and decodes its rows lazily as the stream is polled.
+/// Interceptors and tracing wrap the whole stream: the after hooks run once it ends or fails.
+/// A versioned query that ends without a row yields Neo4gError::ConcurrentModification as its last item.
+pub(crate) fn stream<F, R>(graph: Graph, query: String, params: HashMap<String, BoltType>, return_refs: Vec<(String, EntityType)>, optional_refs: HashSet<String>, versioned: bool, unpack: F) -> impl Stream<Item = Result<Vec<R>>>
+where F: Fn(DbEntityWrapper) -> R {
+    stream_from(graph, query, params, return_refs, optional_refs, versioned, unpack)
+}
+
+/// Opens the rows of a query as a stream that is read as it is polled.
+/// Implemented for Graph, and for fakes in tests.
+trait RowSource {
+    async fn open(&mut self, query: &str, params: &HashMap<String, BoltType>) -> Result<BoxStream<'static, Result<Row>>>;
+}
+
+impl RowSource for Graph {
+    async fn open(&mut self, query: &str, params: &HashMap<String, BoltType>) -> Result<BoxStream<'static, Result<Row>>> {
+        let rows = self.execute(Query::new(query.to_string()).params(params.clone())).await?;
+        Ok(TryStreamExt::into_stream(rows.into_stream()).map_err(Neo4gError::from).boxed())
+    }
+}
+
+fn stream_from<S, F, R>(source: S, query: String, params: HashMap<String, BoltType>, return_refs: Vec<(String, EntityType)>, optional_refs: HashSet<String>, versioned: bool, unpack: F) -> impl Stream<Item = Result<Vec<R>>>
+where S: RowSource, F: Fn(DbEntityWrapper) -> R {
+    let state = RowStreamState {
+        source,
+        context: QueryContext { query, params },
+        return_refs,
+        optional_refs,
+        versioned,
+        unpack,
+        rows: None,
+        span: Span::none(),
+        start: Instant::now(),
+        count: 0,
+        done: false,
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+        if state.rows.is_none() {
+            if let Err(e) = run_before(&mut state.context) {
+                state.done = true;
+                return Some((Err(e), state));
+            }
+            state.span = query_span(&state.context.query, &state.context.params);
+            state.start = Instant::now();
+            match state.source.open(&state.context.query, &state.context.params).instrument(state.span.clone()).await {
+                Ok(rows) => state.rows = Some(rows),
+                Err(e) => {
+                    let e = state.fail(e);
+                    return Some((Err(e), state));
+                },
+            }
+        }
+        let next = match state.rows.as_mut() {
+            Some(rows) => rows.next().instrument(state.span.clone()).await.transpose(),
+            None => Ok(None),
+        };
+        match next {
+            Ok(Some(row)) => match decode_row(&row, &state.return_refs, &state.optional_refs, &state.unpack) {
+                Ok(decoded) => {
+                    state.count += 1;
+                    Some((Ok(decoded), state))
+                },
+                Err(e) => {
+                    let e = state.fail(e);
+                    Some((Err(e), state))
+                },
+            },
+            Ok(None) if state.versioned && state.count == 0 => {
+                let query = state.context.query.clone();
+                let e = state.fail(Neo4gError::ConcurrentModification { query });
+                Some((Err(e), state))
+            },
+            Ok(None) => {
+                state.finish(&QueryOutcome::Rows(state.count));
+                None
+            },
+            Err(e) => {
+                let e = state.fail(e);
+                Some((Err(e), state))
+            },
+        }
+    })
+}
+
+struct RowStreamState<S, F> {
+    source: S,
+    context: QueryContext,
+    return_refs: Vec<(String, EntityType)>,
+    optional_refs: HashSet<String>,
+    versioned: bool,
+    unpack: F,
+    rows: Option<BoxStream<'static, Result<Row>>>,
+    span: Span,
+    start: Instant,
+    count: usize,
+    done: bool,
+}
+
+impl<S, F> RowStreamState<S, F> {
+    fn finish(&mut self, outcome: &QueryOutcome) {
+        self.done = true;
+        record_outcome(&self.span, self.start, outcome);
+        run_after(&self.context, outcome, self.start.elapsed());
+    }
+    fn fail(&mut self, e: Neo4gError) -> Neo4gError {
+        let e = e.with_query(&self.context.query, &self.context.params);
+        self.finish(&QueryOutcome::Error(&e));
+        e
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use neo4rs::{BoltInteger, BoltList, BoltMap, BoltNode};
+
+    use super::*;
+
+    /// Hands out a fixed list of rows and errors, counting how many of them have been read.
+    struct FakeRows {
+        rows: Vec<Result<Row>>,
+        read: Arc<AtomicUsize>,
+    }
+
+    impl RowSource for FakeRows {
+        async fn open(&mut self, _query: &str, _params: &HashMap<String, BoltType>) -> Result<BoxStream<'static, Result<Row>>> {
+            let read = self.read.clone();
+            let rows = std::mem::take(&mut self.rows);
+            Ok(futures::stream::iter(rows).inspect(move |_| { read.fetch_add(1, Ordering::SeqCst); }).boxed())
+        }
+    }
+
+    fn node_row(id: i64) -> Result<Row> {
+        let mut labels = BoltList::new();
+        labels.push("User".into());
+        let node = BoltType::Node(BoltNode { id: BoltInteger::new(id), labels, properties: BoltMap::new() });
+        Ok(Row::new(BoltList::from(vec![BoltType::from("user1")]), BoltList::from(vec![node])))
+    }
+
+    fn user_stream(rows: Vec<Result<Row>>, read: &Arc<AtomicUsize>, decoded: &Arc<AtomicUsize>) -> impl Stream<Item = Result<Vec<i64>>> {
+        let decoded = decoded.clone();
+        let source = FakeRows { rows, read: read.clone() };
+        let return_refs = vec![("user1".to_string(), EntityType::Node)];
+        stream_from(source, "MATCH (user1:User) RETURN user1".to_string(), HashMap::new(), return_refs, HashSet::new(), false, move |entity| {
+            decoded.fetch_add(1, Ordering::SeqCst);
+            match entity {
+                DbEntityWrapper::Node(node) => node.id(),
+                _ => -1,
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn rows_are_read_and_decoded_as_the_stream_is_polled() {
+        let (read, decoded) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let mut rows = std::pin::pin!(user_stream(vec![node_row(1), node_row(2), node_row(3)], &read, &decoded));
+        assert_eq!(rows.next().await.unwrap().unwrap(), vec![1]);
+        assert_eq!((read.load(Ordering::SeqCst), decoded.load(Ordering::SeqCst)), (1, 1));
+        assert_eq!(rows.next().await.unwrap().unwrap(), vec![2]);
+        assert_eq!((read.load(Ordering::SeqCst), decoded.load(Ordering::SeqCst)), (2, 2));
+        assert_eq!(rows.next().await.unwrap().unwrap(), vec![3]);
+        assert!(rows.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn an_error_mid_stream_is_yielded_once_and_ends_the_stream() {
+        let (read, decoded) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let failure = Err(Neo4gError::Db(neo4rs::Error::ConnectionError));
+        let mut rows = std::pin::pin!(user_stream(vec![node_row(1), failure, node_row(3)], &read, &decoded));
+        assert_eq!(rows.next().await.unwrap().unwrap(), vec![1]);
+        assert!(matches!(rows.next().await, Some(Err(e)) if e.is_transient()));
+        assert!(rows.next().await.is_none());
+        assert_eq!((read.load(Ordering::SeqCst), decoded.load(Ordering::SeqCst)), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn a_row_that_fails_to_decode_carries_the_query() {
+        let (read, decoded) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let not_a_node = Ok(Row::new(BoltList::from(vec![BoltType::from("user1")]), BoltList::from(vec![BoltType::from(5)])));
+        let mut rows = std::pin::pin!(user_stream(vec![node_row(1), not_a_node, node_row(3)], &read, &decoded));
+        assert!(rows.next().await.unwrap().is_ok());
+        let e = rows.next().await.unwrap().unwrap_err();
+        assert!(matches!(&e, Neo4gError::Decode { reason, .. } if reason.contains("user1")), "{:?}", e);
+        assert_eq!(e.query(), Some("MATCH (user1:User) RETURN user1"));
+        assert!(rows.next().await.is_none());
+        assert_eq!(read.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+/// Crockford's base32 alphabet, as used by ULIDs.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Returns a random UUID, for fields marked #[neo4g(id = "uuid_v4")].
+pub fn uuid_v4() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Returns a time-ordered UUID, for fields marked #[neo4g(id = "uuid_v7")].
+pub fn uuid_v7() -> String {
+    Uuid::now_v7().to_string()
+}
+
+/// Returns a 26 character ULID, for fields marked #[neo4g(id = "ulid")].
+/// The first 48 bits are the milliseconds since the Unix epoch, so ULIDs sort by creation time; the other 80 are random.
+/// Panics if the operating system's random number generator fails, as Uuid::new_v4 does.
+pub fn ulid() -> String {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or_default();
+    let mut random = [0u8; 16];
+    getrandom::fill(&mut random[6..]).expect("failed to read random bytes for a ULID");
+    let value = ((millis & ((1 << 48) - 1)) << 80) | u128::from_be_bytes(random);
+    (0..26).rev().map(|i| CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(ulid: &str) -> u128 {
+        ulid.bytes().fold(0, |value, c| (value << 5) | CROCKFORD.iter().position(|&d| d == c).unwrap() as u128)
+    }
+
+    #[test]
+    fn ulid_is_26_crockford_characters() {
+        let id = ulid();
+        assert_eq!(id.len(), 26);
+        assert!(id.bytes().all(|c| CROCKFORD.contains(&c)), "{}", id);
+        // 128 bits fit in 26 characters with two to spare, so the first is at most 7.
+        assert!(id.as_bytes()[0] <= b'7', "{}", id);
+    }
+
+    #[test]
+    fn ulid_starts_with_the_current_time() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        let millis = decode(&ulid()) >> 80;
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        assert!(before <= millis && millis <= after, "{} not in {}..={}", millis, before, after);
+    }
+
+    #[test]
+    fn ulid_uses_all_80_random_bits() {
+        let seen = (0..64).map(|_| decode(&ulid()) & ((1 << 80) - 1)).fold(0, |seen, random| seen | random);
+        assert_eq!(seen, (1 << 80) - 1);
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use neo4rs::BoltType;
+
+use crate::query_builder::{Neo4gError, Result};
+
+static INTERCEPTORS: RwLock<Vec<Arc<dyn Interceptor>>> = RwLock::new(Vec::new());
+
+/// The final query and params of an execution, as seen by interceptors.
+#[derive(Debug, Clone)]
+pub struct QueryContext {
+    pub query: String,
+    pub params: HashMap<String, BoltType>,
+}
+
+/// What an execution produced, as seen by interceptors.
+#[derive(Debug)]
+pub enum QueryOutcome<'a> {
+    Rows(usize),
+    Error(&'a Neo4gError),
+}
+
+/// Runs before and after every run_query, execute and execute_in_txn, on builders and prepared queries alike.
+/// Repositories, batches, Neo4gSchema::ensure and the queries of migrations run through them too.
+/// Only the migration runner's own bookkeeping queries bypass them. See Migrator.
+/// # Example:
+/// ```rust
+/// struct Tenancy;
+/// impl Interceptor for Tenancy {
+///     fn before(&self, context: &mut QueryContext) -> std::result::Result<(), String> {
+///         if context.query.contains("DETACH DELETE") {
+///             return Err("deletes are not allowed from the web tier".into());
+///         }
+///         context.params.insert("tenant".into(), current_tenant().into());
+///         Ok(())
+///     }
+/// }
+/// register_interceptor(Tenancy);
+/// ```
+pub trait Interceptor: Send + Sync {
+    /// Called with the final query and params before they are sent. The query and params can be rewritten here.
+    /// Returning an Err vetoes the execution, which then fails with Neo4gError::Vetoed.
+    fn before(&self, _context: &mut QueryContext) -> std::result::Result<(), String> {
+        Ok(())
+    }
+    /// Called with the query and params that were sent, the row count or error, and how long the execution took.
+    fn after(&self, _context: &QueryContext, _outcome: &QueryOutcome, _elapsed: Duration) {}
+}
+
+/// Registers an interceptor for every execution from now on. Interceptors run in the order they were registered.
+pub fn register_interceptor<I: Interceptor + 'static>(interceptor: I) {
+    INTERCEPTORS.write().unwrap_or_else(|e| e.into_inner()).push(Arc::new(interceptor));
+}
+
+/// Removes every registered interceptor.
+pub fn clear_interceptors() {
+    INTERCEPTORS.write().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+fn interceptors() -> Vec<Arc<dyn Interceptor>> {
+    INTERCEPTORS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub(crate) fn run_before(context: &mut QueryContext) -> Result<()> {
+    for interceptor in interceptors() {
+        interceptor.before(context).map_err(|reason| Neo4gError::Vetoed { reason })?;
+    }
+    Ok(())
+}
+
+pub(crate) fn run_after(context: &QueryContext, outcome: &QueryOutcome, elapsed: Duration) {
+    for interceptor in interceptors() {
+        interceptor.after(context, outcome, elapsed);
+    }
+}
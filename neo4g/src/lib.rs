@@ -1,5 +1,21 @@
 //! Document library here!!!
 
 pub mod query_builder;
+pub mod prepared_query;
+pub mod batch;
+pub mod sync;
+pub mod repository;
+pub mod ids;
+pub mod timestamps;
+pub mod schema;
+pub mod migrations;
+pub mod render;
+pub mod telemetry;
+pub mod interceptor;
+pub mod executor;
+pub mod replay;
+pub mod transaction;
 pub mod traits;
-pub mod prelude;
\ No newline at end of file
+pub mod prelude;
+
+pub use transaction::{transaction, transaction_with};
\ No newline at end of file
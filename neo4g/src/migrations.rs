@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+use neo4rs::{BoltType, Graph, Query, Row, Txn};
+
+use crate::executor::execute_rows;
+use crate::query_builder::{Neo4gBuilder, Neo4gError, Result};
+use crate::render::{sort_return_aliases, Rendered};
+use crate::traits::PossibleQueryEnd;
+
+/// A single versioned change to the graph, made up of one or more ordered steps.
+/// Steps are either raw Cypher or queries built with Neo4gBuilder.
+/// # Example
+/// ```rust
+/// let migration = Migration::new(3, "rename user forename")
+///     .cypher("MATCH (u:User) WHERE u.first_name IS NOT NULL SET u.forename = u.first_name REMOVE u.first_name")
+///     .query(Neo4gBuilder::new()
+///         .get()
+///             .node(&mut page, props!(page => page.path))
+///             .set(&page, &[PageProps::Path("/home".into())])
+///         .end_statement());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Migration {
+    version: i64,
+    name: String,
+    steps: Vec<(String, HashMap<String, BoltType>)>,
+}
+
+impl Migration {
+    pub fn new(version: i64, name: &str) -> Self {
+        Self {
+            version,
+            name: name.to_string(),
+            steps: Vec::new(),
+        }
+    }
+    /// Adds a raw Cypher step.
+    pub fn cypher(self, query: &str) -> Self {
+        self.cypher_with_params(query, HashMap::new())
+    }
+    /// Adds a raw Cypher step with params.
+    pub fn cypher_with_params(mut self, query: &str, params: HashMap<String, BoltType>) -> Self {
+        self.steps.push((query.to_string(), params));
+        self
+    }
+    /// Adds a step built with Neo4gBuilder.
+    pub fn query<Q: PossibleQueryEnd + Debug>(mut self, builder: Neo4gBuilder<Q>) -> Self {
+        self.steps.push(builder.prepare().build());
+        self
+    }
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// A stable FNV-1a checksum over the canonical form of every step.
+    /// Steps are canonicalized before hashing: condition params are renumbered in order of appearance and RETURN aliases are sorted.
+    /// Only the names and types of params are hashed, not their values, so a step that creates an entity with a generated id
+    /// or the current time gives the same checksum every time it is built.
+    /// Changing the Cypher of an applied migration, or the names or types of its params, changes its checksum, which .verify() reports.
+    pub fn checksum(&self) -> String {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+        for (query, params) in &self.steps {
+            let types: HashMap<String, BoltType> = params.iter().map(|(key, value)| (key.clone(), BoltType::from(type_name(value)))).collect();
+            let canonical = Rendered::new(&sort_return_aliases(query), &types);
+            feed(canonical.query.as_bytes());
+            for (key, value) in &canonical.params {
+                feed(key.as_bytes());
+                feed(b":");
+                feed(value.as_bytes());
+                feed(b";");
+            }
+            feed(b"\n");
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+/// The Bolt type of a param, which is hashed into checksums in place of its value.
+fn type_name(value: &BoltType) -> &'static str {
+    match value {
+        BoltType::String(_) => "String",
+        BoltType::Boolean(_) => "Boolean",
+        BoltType::Map(_) => "Map",
+        BoltType::Null(_) => "Null",
+        BoltType::Integer(_) => "Integer",
+        BoltType::Float(_) => "Float",
+        BoltType::List(_) => "List",
+        BoltType::Node(_) => "Node",
+        BoltType::Relation(_) => "Relation",
+        BoltType::UnboundedRelation(_) => "UnboundedRelation",
+        BoltType::Point2D(_) => "Point2D",
+        BoltType::Point3D(_) => "Point3D",
+        BoltType::Bytes(_) => "Bytes",
+        BoltType::Path(_) => "Path",
+        BoltType::Duration(_) => "Duration",
+        BoltType::Date(_) => "Date",
+        BoltType::Time(_) => "Time",
+        BoltType::LocalTime(_) => "LocalTime",
+        BoltType::DateTime(_) => "DateTime",
+        BoltType::LocalDateTime(_) => "LocalDateTime",
+        BoltType::DateTimeZoneId(_) => "DateTimeZoneId",
+    }
+}
+
+/// The state of a migration relative to the database.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    ChecksumMismatch { expected: String, found: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub state: MigrationState,
+}
+
+/// How long a migration lock is held without a migration being applied before it is treated as stale, when no lock TTL is set.
+pub const DEFAULT_LOCK_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Applies migrations in version order and records each one as a :__Neo4gMigration node.
+/// Only one runner can apply migrations at a time; the others fail with Neo4gError::MigrationLocked.
+/// The lock is refreshed after each applied migration, and a lock that hasn't been refreshed within the lock TTL is treated as
+/// left behind by a runner that crashed, so the next runner takes it over.
+/// The migrations' own queries run through the registered interceptors, like any other execution.
+/// The bookkeeping queries on the :__Neo4gMigration and :__Neo4gMigrationLock nodes don't, so an interceptor can't veto or rewrite the lock.
+/// # Example
+/// ```rust
+/// let migrator = Migrator::new(vec![
+///     Migration::new(1, "user ids").cypher("MATCH (u:User) WHERE u.id IS NULL SET u.id = randomUUID()"),
+///     Migration::new(2, "page paths").cypher("MATCH (p:Page) SET p.path = toLower(p.path)"),
+/// ]);
+/// let applied = migrator.up(&graph).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Migrator {
+    migrations: Vec<Migration>,
+    lock_ttl: Duration,
+}
+
+impl Migrator {
+    pub fn new(mut migrations: Vec<Migration>) -> Self {
+        migrations.sort_by_key(|m| m.version);
+        Self {
+            migrations,
+            lock_ttl: DEFAULT_LOCK_TTL,
+        }
+    }
+    /// Sets how long the lock can go without being refreshed before another runner takes it over. Defaults to DEFAULT_LOCK_TTL.
+    /// It has to be longer than the slowest migration, as the lock is only refreshed between migrations.
+    pub fn lock_ttl(mut self, lock_ttl: Duration) -> Self {
+        self.lock_ttl = lock_ttl;
+        self
+    }
+    /// Applies every pending migration, each in its own transaction, and returns the applied versions.
+    /// Verifies already applied migrations first, so a changed migration stops the run before anything new is applied.
+    pub async fn up(&self, graph: &Graph) -> Result<Vec<i64>> {
+        self.check_versions()?;
+        acquire_lock(graph, self.lock_ttl).await?;
+        let result = self.apply_pending(graph).await;
+        let released = release_lock(graph).await;
+        let applied = result?;
+        released?;
+        Ok(applied)
+    }
+    /// Releases the lock whether or not it is held, for when a runner has crashed and waiting for the lock TTL isn't an option.
+    /// Only call it when no other runner is applying migrations.
+    pub async fn force_unlock(&self, graph: &Graph) -> Result<()> {
+        release_lock(graph).await
+    }
+    /// Returns the state of every known migration.
+    pub async fn status(&self, graph: &Graph) -> Result<Vec<MigrationStatus>> {
+        let applied = applied_migrations(graph).await?;
+        Ok(self.migrations.iter().map(|m| {
+            let state = match applied.get(&m.version) {
+                None => MigrationState::Pending,
+                Some(found) if *found == m.checksum() => MigrationState::Applied,
+                Some(found) => MigrationState::ChecksumMismatch { expected: m.checksum(), found: found.clone() },
+            };
+            MigrationStatus { version: m.version, name: m.name.clone(), state }
+        }).collect())
+    }
+    /// Checks that every applied migration is known and unchanged.
+    /// Returns Neo4gError::MigrationChecksumMismatch or Neo4gError::UnknownMigration otherwise.
+    pub async fn verify(&self, graph: &Graph) -> Result<()> {
+        self.check_versions()?;
+        let applied = applied_migrations(graph).await?;
+        self.verify_applied(&applied)
+    }
+    fn check_versions(&self) -> Result<()> {
+        for pair in self.migrations.windows(2) {
+            if pair[0].version == pair[1].version {
+                return Err(Neo4gError::DuplicateMigration { version: pair[0].version });
+            }
+        }
+        Ok(())
+    }
+    fn verify_applied(&self, applied: &HashMap<i64, String>) -> Result<()> {
+        for (version, found) in applied {
+            match self.migrations.iter().find(|m| m.version == *version) {
+                None => return Err(Neo4gError::UnknownMigration { version: *version }),
+                Some(m) if m.checksum() != *found => {
+                    return Err(Neo4gError::MigrationChecksumMismatch { version: *version, expected: m.checksum(), found: found.clone() });
+                },
+                Some(_) => {},
+            }
+        }
+        Ok(())
+    }
+    async fn apply_pending(&self, graph: &Graph) -> Result<Vec<i64>> {
+        let applied = applied_migrations(graph).await?;
+        self.verify_applied(&applied)?;
+        let mut newly_applied = Vec::new();
+        for migration in self.migrations.iter().filter(|m| !applied.contains_key(&m.version)) {
+            let mut txn = graph.start_txn().await?;
+            if let Err(e) = apply(&mut txn, migration).await {
+                // The step's error is the one worth reporting, so a failed rollback is only logged.
+                if let Err(rollback_error) = txn.rollback().await {
+                    tracing::warn!(error = %rollback_error, "failed to roll back migration {}", migration.version);
+                }
+                return Err(e);
+            }
+            txn.commit().await?;
+            newly_applied.push(migration.version);
+            touch_lock(graph).await?;
+        }
+        Ok(newly_applied)
+    }
+}
+
+/// Runs the steps of a migration and records it as applied, within the migration's transaction.
+async fn apply(txn: &mut Txn, migration: &Migration) -> Result<()> {
+    for (query, params) in &migration.steps {
+        execute_rows(&mut *txn, query.clone(), params.clone(), |_| Ok(())).await?;
+    }
+    txn.run(Query::new("CREATE (:__Neo4gMigration {version: $version, name: $name, checksum: $checksum, applied_at: datetime()})".to_string())
+        .param("version", migration.version)
+        .param("name", migration.name.clone())
+        .param("checksum", migration.checksum())).await?;
+    Ok(())
+}
+
+async fn applied_migrations(graph: &Graph) -> Result<HashMap<i64, String>> {
+    let mut applied = HashMap::new();
+    let mut result = graph.execute(Query::new("MATCH (m:__Neo4gMigration) RETURN m.version AS version, m.checksum AS checksum".to_string())).await?;
+    while let Some(row) = result.next().await? {
+        applied.insert(get_field::<i64>(&row, "version")?, get_field::<String>(&row, "checksum")?);
+    }
+    Ok(applied)
+}
+
+/// Takes the write lock on the lock node before reading its state, so two runners can't both acquire it.
+/// A lock that hasn't been touched within the TTL is stale, and is taken over.
+async fn acquire_lock(graph: &Graph, ttl: Duration) -> Result<()> {
+    let query = "MERGE (l:__Neo4gMigrationLock {id: 'lock'})
+SET l.locked = coalesce(l.locked, false)
+WITH l, l.locked AND coalesce(l.touched_at > datetime() - duration({seconds: $ttl}), false) AS held
+SET l.locked = true, l.touched_at = CASE WHEN held THEN l.touched_at ELSE datetime() END
+RETURN held";
+    let mut result = graph.execute(Query::new(query.to_string()).param("ttl", ttl.as_secs() as i64)).await?;
+    match result.next().await? {
+        Some(row) if !get_field::<bool>(&row, "held")? => Ok(()),
+        _ => Err(Neo4gError::MigrationLocked),
+    }
+}
+
+/// Refreshes the lock, so a long run of migrations isn't mistaken for a crashed one.
+async fn touch_lock(graph: &Graph) -> Result<()> {
+    graph.run(Query::new("MATCH (l:__Neo4gMigrationLock {id: 'lock'}) SET l.touched_at = datetime()".to_string())).await?;
+    Ok(())
+}
+
+async fn release_lock(graph: &Graph) -> Result<()> {
+    graph.run(Query::new("MATCH (l:__Neo4gMigrationLock {id: 'lock'}) SET l.locked = false".to_string())).await?;
+    Ok(())
+}
+
+fn get_field<'a, T: serde::Deserialize<'a>>(row: &'a Row, key: &str) -> Result<T> {
+    row.get::<T>(key).map_err(|e| Neo4gError::Db(neo4rs::Error::DeserializationError(e)))
+}
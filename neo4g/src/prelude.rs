@@ -2,6 +2,7 @@ pub use crate::{
     query_builder::{
         Neo4gBuilder,
         Where,
+        Raw,
         Unwinder,
         FunctionCall,
         Function,
@@ -13,14 +14,70 @@ pub use crate::{
         RefType,
         EntityType,
         DbEntityWrapper,
+        optional,
     },
+    prepared_query::PreparedQuery,
+    batch::Neo4gBatch,
+    repository::{
+        Neo4gRepository,
+        Paging,
+    },
+    sync::{
+        RelationSync,
+        SyncOptions,
+        SyncReport,
+    },
+    executor::Executor,
+    transaction::{
+        transaction,
+        transaction_with,
+        RetryPolicy,
+    },
+    render::{
+        Rendered,
+        assert_cypher_snapshot,
+    },
+    replay::{
+        RecordingExecutor,
+        ReplayExecutor,
+        Recording,
+        RecordedValue,
+    },
+    telemetry::{
+        ParamPolicy,
+        set_param_policy,
+    },
+    interceptor::{
+        Interceptor,
+        QueryContext,
+        QueryOutcome,
+        register_interceptor,
+        clear_interceptors,
+    },
+    schema::{
+        Neo4gSchema,
+        EntitySchema,
+        SchemaItem,
+    },
+    migrations::{
+        Migration,
+        Migrator,
+        MigrationState,
+        MigrationStatus,
+    },
+    ids,
     traits::{
         Aliasable,
         Paramable,
         Neo4gEntity,
+        NodeEntity,
+        KeyedNode,
         WrappedNeo4gEntity,
         QueryParam,
         Prop,
+        BeforeSave,
+        AfterLoad,
+        BeforeDelete,
     }
 };
 
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::Stream;
+use neo4rs::{BoltType, Graph, Txn};
+
+use crate::executor::{execute, stream, Executor};
+use crate::query_builder::{DbEntityWrapper, EntityType, Neo4gError, Result};
+use crate::render::{browser_script, pretty_cypher, Rendered};
+use crate::traits::{Aliasable, QueryParam};
+
+/// The property a SET or condition param holds, and the alias of the entity it is set on or compared with.
+/// Recorded by the builder as it names the params, so prepared queries can rebind them by prop.
+#[derive(Debug, Clone)]
+pub(crate) struct ParamSlot {
+    pub(crate) prop: String,
+    pub(crate) alias: Option<String>,
+}
+
+/// A query that has been built once by a Neo4gBuilder and can be run repeatedly.
+/// Every param generated by the builder is a named slot that can be rebound before each run,
+/// so hot paths only pay for swapping values rather than rebuilding the query string.
+#[derive(Debug, Clone)]
+pub struct PreparedQuery {
+    query: String,
+    params: HashMap<String, BoltType>,
+    param_slots: HashMap<String, ParamSlot>,
+    return_refs: Vec<(String, EntityType)>,
+    optional_refs: HashSet<String>,
+    entity_aliases: Vec<String>,
+    versioned: bool,
+}
+
+impl PreparedQuery {
+    pub(crate) fn new(query: String, params: HashMap<String, BoltType>, param_slots: HashMap<String, ParamSlot>, return_refs: Vec<(String, EntityType)>, optional_refs: HashSet<String>, entity_aliases: Vec<String>, versioned: bool) -> Self {
+        Self {
+            query,
+            params,
+            param_slots,
+            return_refs,
+            optional_refs,
+            entity_aliases,
+            versioned,
+        }
+    }
+    /// Returns the names of the param slots in the query.
+    pub fn slots(&self) -> Vec<&str> {
+        let mut slots: Vec<&str> = self.params.keys().map(|k| k.as_str()).collect();
+        slots.sort();
+        slots
+    }
+    /// Binds a new value to every slot that was generated from the same property of the prop's entity.
+    /// This covers node and relation props, SET props and WHERE conditions.
+    /// # Example
+    /// ```rust
+    /// prepared.bind(UserProps::Name("admin".into()))?;
+    /// ```
+    /// Only slots of entities with the prop's label are bound, so PageProps::Id never binds a Component's id.
+    /// Returns Neo4gError::AmbiguousSlot if the slots belong to more than one entity, in which case use .bind_for(),
+    /// and Neo4gError::UnknownSlot if no slot was generated from the property.
+    pub fn bind<P: QueryParam>(&mut self, prop: P) -> Result<&mut Self> {
+        let label = prop.entity_label();
+        let (key, value) = prop.to_query_param();
+        let aliases: Vec<&str> = self.entity_aliases.iter()
+            .map(|alias| alias.as_str())
+            .filter(|alias| label.as_deref().is_none_or(|label| is_alias_of(alias, label)))
+            .collect();
+        let mut owners: Vec<&str> = Vec::new();
+        let mut slots: Vec<String> = Vec::new();
+        for slot in self.params.keys() {
+            let owner = if let Some(alias) = aliases.iter().find(|alias| is_entity_slot(slot, alias, key)) {
+                Some(*alias)
+            } else if let Some(param_slot) = self.param_slots.get(slot).filter(|param_slot| param_slot.prop == key) {
+                match param_slot.alias.as_deref() {
+                    Some(owner) if !aliases.contains(&owner) => continue,
+                    owner => owner,
+                }
+            } else {
+                continue;
+            };
+            if let Some(owner) = owner.filter(|owner| !owners.contains(owner)) {
+                owners.push(owner);
+            }
+            slots.push(slot.clone());
+        }
+        if owners.len() > 1 {
+            owners.sort();
+            return Err(Neo4gError::AmbiguousSlot { slot: key.to_string(), aliases: owners.iter().map(|o| o.to_string()).collect() });
+        }
+        self.bind_slots(slots, key, value)
+    }
+    /// Binds a new value to the slot generated from a property of a specific entity.
+    /// Use this when the same property is used by more than one entity in the query.
+    /// # Example
+    /// ```rust
+    /// prepared.bind_for(&user, UserProps::Id(id))?;
+    /// ```
+    pub fn bind_for<A: Aliasable, P: QueryParam>(&mut self, entity: &A, prop: P) -> Result<&mut Self> {
+        let (key, value) = prop.to_query_param();
+        let slot = format!("{}_{}", entity.get_alias().to_lowercase(), key);
+        let slots: Vec<String> = self.params.keys().filter(|s| **s == slot).cloned().collect();
+        self.bind_slots(slots, &slot, value)
+    }
+    /// Binds a new value to a slot by its exact name, as listed by .slots().
+    /// # Example
+    /// ```rust
+    /// prepared.bind_param("member_ofs", group_ids)?;
+    /// ```
+    pub fn bind_param<V: Into<BoltType>>(&mut self, slot: &str, value: V) -> Result<&mut Self> {
+        match self.params.get_mut(slot) {
+            Some(param) => {
+                *param = value.into();
+                Ok(self)
+            },
+            None => Err(Neo4gError::UnknownSlot { slot: slot.to_string() }),
+        }
+    }
+    /// Returns the query and the currently bound params.
+    pub fn build(&self) -> (String, HashMap<String, BoltType>) {
+        (self.query.clone(), self.params.clone())
+    }
+    /// Returns the query indented with one clause per line.
+    pub fn to_cypher_pretty(&self) -> String {
+        pretty_cypher(&self.query)
+    }
+    /// Returns the query and currently bound params in a normalized form that is stable between runs, for snapshot tests.
+    pub fn render(&self) -> Rendered {
+        Rendered::new(&self.query, &self.params)
+    }
+    /// Returns a script of :param lines with the currently bound values followed by the query,
+    /// ready to paste into Neo4j Browser or cypher-shell.
+    pub fn to_browser_script(&self) -> String {
+        browser_script(&self.query, &self.params)
+    }
+    /// Runs the query against a provided Graph and returns the registered return objects in nested Vecs.
+    /// The outer Vec contains Vecs that represent rows. The inner Vec contains wrapped entities within each row.
+    /// # Example:
+    /// ```rust
+    /// prepared.run_query(graph, EntityWrapper::from_db_entity).await;
+    /// ```
+    pub async fn run_query<F, R>(&self, mut graph: Graph, unpack: F) -> Result<Vec<Vec<R>>>
+    where F: Fn(DbEntityWrapper) -> R {
+        self.run_with(&mut graph, unpack).await
+    }
+    /// Runs the query against a provided Txn and returns the registered return objects in nested Vecs.
+    /// The outer Vec contains Vecs that represent rows. The inner Vec contains wrapped entities within each row.
+    /// # Example:
+    /// ```rust
+    /// prepared.execute_in_txn(&mut txn, EntityWrapper::from_db_entity).await;
+    /// ```
+    pub async fn execute_in_txn<F, R>(&self, txn: &mut Txn, unpack: F) -> Result<Vec<Vec<R>>>
+    where F: Fn(DbEntityWrapper) -> R {
+        self.run_with(txn, unpack).await
+    }
+    /// Runs the query against a provided Graph and returns a stream of rows, decoded lazily as the stream is polled.
+    /// # Example:
+    /// ```rust
+    /// let mut rows = std::pin::pin!(prepared.stream(graph, EntityWrapper::from_db_entity));
+    /// while let Some(row) = rows.next().await {
+    ///     let row = row?;
+    /// }
+    /// ```
+    pub fn stream<F, R>(&self, graph: Graph, unpack: F) -> impl Stream<Item = Result<Vec<R>>>
+    where F: Fn(DbEntityWrapper) -> R {
+        stream(graph, self.query.clone(), self.params.clone(), self.return_refs.clone(), self.optional_refs.clone(), self.versioned, unpack)
+    }
+    /// Runs the query against any Executor and returns the registered return objects in nested Vecs.
+    /// # Example:
+    /// ```rust
+    /// prepared.run_with(&mut recording_executor, EntityWrapper::from_db_entity).await;
+    /// ```
+    /// Returns Neo4gError::ConcurrentModification if the query sets a versioned entity and no rows match.
+    pub async fn run_with<E, F, R>(&self, executor: &mut E, unpack: F) -> Result<Vec<Vec<R>>>
+    where E: Executor, F: Fn(DbEntityWrapper) -> R {
+        let rows = execute(executor, self.query.clone(), self.params.clone(), &self.return_refs, &self.optional_refs, unpack).await?;
+        if self.versioned && rows.is_empty() {
+            return Err(Neo4gError::ConcurrentModification { query: self.query.clone() });
+        }
+        Ok(rows)
+    }
+    fn bind_slots(&mut self, slots: Vec<String>, key: &str, value: BoltType) -> Result<&mut Self> {
+        if slots.is_empty() {
+            return Err(Neo4gError::UnknownSlot { slot: key.to_string() });
+        }
+        for slot in slots {
+            self.params.insert(slot, value.clone());
+        }
+        Ok(self)
+    }
+}
+
+/// Aliases are generated from the lowercased label followed by a number, e.g. page1 for Page or member_of2 for MEMBER_OF.
+fn is_alias_of(alias: &str, label: &str) -> bool {
+    alias.strip_prefix(label.to_lowercase().as_str())
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Slots generated by node_by and relation_by are named aliasname_propname.
+fn is_entity_slot(slot: &str, alias: &str, key: &str) -> bool {
+    slot.strip_prefix(alias.to_lowercase().as_str())
+        .and_then(|rest| rest.strip_prefix('_'))
+        .is_some_and(|rest| rest == key)
+}
@@ -1,14 +1,22 @@
 //use anyhow::anyhow; // should I use thiserror instead? prolly...
 use thiserror::Error;
-use neo4rs::{BoltType, Graph, Node, Query, Relation, Txn};
+use neo4rs::{BoltType, Graph, Node, Relation, Row, Txn};
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 use std::fmt::{self, Debug};
 use std::vec;
 use uuid::Uuid;
 use crate::traits::*;
+use crate::prepared_query::{ParamSlot, PreparedQuery};
+use crate::render::{browser_script, pretty_cypher, Rendered};
+use crate::executor::{execute, stream, Executor};
+use crate::timestamps;
+use futures::Stream;
 pub type Result<T> = std::result::Result<T, Neo4gError>;
 
+/// The prefix of the params that hold the version an entity was read at, as checked by versioned SET calls.
+pub(crate) const VERSION_PARAM_PREFIX: &str = "expected_version_";
+
 #[derive(Debug, Error)]
 pub enum Neo4gError {
     #[error("failed to get node from db for alias '{alias}'")]
@@ -17,23 +25,131 @@ pub enum Neo4gError {
     RelationNotFound {alias: String},
     #[error("the item with alias '{alias}' is not a node or a relation, which should never happen")]
     UnexpectedError {alias: String},
+    #[error("no parameter slot in the prepared query matches '{slot}'")]
+    UnknownSlot {slot: String},
+    #[error("the property '{slot}' matches slots of more than one entity ({aliases:?}), bind it with bind_for instead")]
+    AmbiguousSlot {slot: String, aliases: Vec<String>},
+    #[error("another runner holds the migration lock")]
+    MigrationLocked,
+    #[error("migration {version} has changed since it was applied (expected checksum {expected}, found {found})")]
+    MigrationChecksumMismatch {version: i64, expected: String, found: String},
+    #[error("migration {version} has been applied but is not in the list of known migrations")]
+    UnknownMigration {version: i64},
+    #[error("more than one migration has version {version}")]
+    DuplicateMigration {version: i64},
+    #[error("the query part references an entity that has not been used in the query: {part}")]
+    UnknownEntity {part: String},
+    #[error("the raw param '{param}' is already in use by the query")]
+    ParamCollision {param: String},
+    #[error("execution vetoed by an interceptor: {reason}")]
+    Vetoed {reason: String},
+    #[error("no replay answer for query: {query}")]
+    NoReplay {query: String},
+    #[error("failed to read or write a recording: {reason}")]
+    Replay {reason: String},
+    #[error("constraint violated on label {label:?}, property {property:?}: {message}")]
+    ConstraintViolation {label: Option<String>, property: Option<String>, message: String, query: String, param_names: Vec<String>},
+    #[error("invalid Cypher ({code}): {message}")]
+    Syntax {code: String, message: String, query: String, param_names: Vec<String>},
+    #[error("transient failure ({code}), the query can be retried: {message}")]
+    Transient {code: String, message: String, query: String, param_names: Vec<String>},
+    #[error("authentication or authorization failed: {message}")]
+    Auth {message: String, query: String, param_names: Vec<String>},
+    #[error("the entity was modified or deleted since it was read, as its version no longer matches")]
+    ConcurrentModification {query: String},
+    #[error("failed to decode the result: {reason}")]
+    Decode {reason: String, query: String, param_names: Vec<String>},
+    #[error("the commit failed, so the transaction may or may not have been applied: {source}")]
+    CommitOutcomeUnknown {query: String, #[source] source: Box<Neo4gError>},
     #[error(transparent)]
     Db(#[from] neo4rs::Error),
 }
 
+impl Neo4gError {
+    /// Returns true if the error is temporary and the work can be retried, e.g. a deadlock or a lost connection.
+    /// Failed commits are never transient, as the work may already have been applied.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Neo4gError::Transient { .. } => true,
+            Neo4gError::Db(neo4rs::Error::Neo4j(e)) => matches!(e.kind(), neo4rs::Neo4jErrorKind::Transient),
+            Neo4gError::Db(neo4rs::Error::ConnectionError) | Neo4gError::Db(neo4rs::Error::IOError { .. }) => true,
+            _ => false,
+        }
+    }
+    /// Returns the query text that caused the error, if it is known.
+    pub fn query(&self) -> Option<&str> {
+        match self {
+            Neo4gError::ConstraintViolation { query, .. }
+            | Neo4gError::Syntax { query, .. }
+            | Neo4gError::Transient { query, .. }
+            | Neo4gError::Auth { query, .. }
+            | Neo4gError::ConcurrentModification { query, .. }
+            | Neo4gError::Decode { query, .. }
+            | Neo4gError::CommitOutcomeUnknown { query, .. } => Some(query),
+            _ => None,
+        }
+    }
+    /// Classifies server and decode errors and attaches the query and param names that caused them.
+    /// Other errors are returned unchanged.
+    pub(crate) fn with_query(self, query: &str, params: &HashMap<String, BoltType>) -> Self {
+        let mut param_names: Vec<String> = params.keys().cloned().collect();
+        param_names.sort();
+        let query = query.to_string();
+        match self {
+            Neo4gError::Db(neo4rs::Error::Neo4j(e)) => {
+                server_error(e.code(), e.message(), query, param_names).unwrap_or(Neo4gError::Db(neo4rs::Error::Neo4j(e)))
+            },
+            Neo4gError::Db(neo4rs::Error::AuthenticationError(message)) => Neo4gError::Auth { message, query, param_names },
+            Neo4gError::Db(neo4rs::Error::DeserializationError(e)) => Neo4gError::Decode { reason: e.to_string(), query, param_names },
+            e @ (Neo4gError::NodeNotFound { .. } | Neo4gError::RelationNotFound { .. } | Neo4gError::UnexpectedError { .. }) => {
+                Neo4gError::Decode { reason: e.to_string(), query, param_names }
+            },
+            e => e,
+        }
+    }
+}
+
+/// Classifies a server error by its code, or returns None for codes that have no variant of their own.
+fn server_error(code: &str, message: &str, query: String, param_names: Vec<String>) -> Option<Neo4gError> {
+    let (code, message) = (code.to_string(), message.to_string());
+    if code == "Neo.ClientError.Schema.ConstraintValidationFailed" {
+        let label = backticked_after(&message, "label `");
+        let property = backticked_after(&message, "property `").or_else(|| backticked_after(&message, "properties `"));
+        return Some(Neo4gError::ConstraintViolation { label, property, message, query, param_names });
+    }
+    if code.starts_with("Neo.ClientError.Statement.") && (code.ends_with("SyntaxError") || code.ends_with("ParameterMissing") || code.ends_with("TypeError")) {
+        return Some(Neo4gError::Syntax { code, message, query, param_names });
+    }
+    match neo4rs::Neo4jErrorKind::from(code.as_str()) {
+        neo4rs::Neo4jErrorKind::Transient => Some(Neo4gError::Transient { code, message, query, param_names }),
+        neo4rs::Neo4jErrorKind::Client(neo4rs::Neo4jClientErrorKind::Security(_)) => Some(Neo4gError::Auth { message, query, param_names }),
+        _ => None,
+    }
+}
+
+/// Returns the text between the backticks that follow a marker, e.g. User from "with label `User`".
+fn backticked_after(message: &str, marker: &str) -> Option<String> {
+    let start = message.find(marker)? + marker.len();
+    let end = message[start..].find('`')? + start;
+    Some(message[start..end].to_string())
+}
+
 use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 pub struct Neo4gBuilder<State> {
     query: String,
     params: HashMap<String, BoltType>,
+    param_slots: HashMap<String, ParamSlot>,
     entity_aliases: HashMap<Uuid, String>,
     node_number: u32,
     relation_number: u32,
     unwind_number: u32,
     set_number: u32,
     with_number: u32,
-    return_refs: HashSet<(String, EntityType)>,
+    return_refs: Vec<(String, EntityType)>,
+    optional_refs: HashSet<String>,
+    hooked: HashSet<Uuid>,
     order_by_str: String,
     previous_entity: Option<(String, EntityType)>,
     clause: Clause,
@@ -47,13 +163,16 @@ impl Neo4gBuilder<Empty> {
         Self {
             query: String::new(),
             params: HashMap::new(),
+            param_slots: HashMap::new(),
             entity_aliases: HashMap::new(),
             node_number: 0,
             relation_number: 0,
             unwind_number: 0,
             set_number: 0,
             with_number: 0,
-            return_refs: HashSet::new(),
+            return_refs: Vec::new(),
+            optional_refs: HashSet::new(),
+            hooked: HashSet::new(),
             order_by_str: String::new(),
             previous_entity: None,
             clause: Clause::None,
@@ -65,13 +184,16 @@ impl Neo4gBuilder<Empty> {
         Self {
             query: String::new(),
             params: HashMap::new(),
+            param_slots: HashMap::new(),
             entity_aliases: parent.entity_aliases.clone(),
             node_number: parent.node_number,
             relation_number: parent.relation_number,
             unwind_number: parent.unwind_number,
             set_number: parent.set_number,
             with_number: parent.with_number,
-            return_refs: HashSet::new(),
+            return_refs: Vec::new(),
+            optional_refs: HashSet::new(),
+            hooked: parent.hooked.clone(),
             order_by_str: String::new(),
             previous_entity: None,
             clause: Clause::None,
@@ -166,6 +288,7 @@ impl<Q: CanMatch+Debug> Neo4gBuilder<Q> {
         let (
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -173,14 +296,17 @@ impl<Q: CanMatch+Debug> Neo4gBuilder<Q> {
             set_number,
             with_number,
             return_refs,
+            hooked,
         ) = inner_builder_closure(inner_builder).build_inner();
+        self.param_slots.extend(param_slots);
         self.entity_aliases.extend(entity_aliases);
+        self.hooked.extend(hooked);
         self.node_number = node_number;
         self.relation_number = relation_number;
         self.set_number = set_number;
         self.with_number = with_number;
         self.unwind_number = unwind_number;
-        return_refs.iter().map(|i| self.return_refs.insert(i.clone()));
+        return_refs.into_iter().for_each(|i| push_return_ref(&mut self.return_refs, i));
         self.query.push_str(format!("\nCALL {{{} \n}}", &query).as_str());
         //self.query.push_str(format!("\nCALL {{\n{}\n}}", &query).as_str());
         self.params.extend(params);
@@ -213,6 +339,7 @@ impl<Q: CanMatch+Debug> Neo4gBuilder<Q> {
         let (
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -220,14 +347,17 @@ impl<Q: CanMatch+Debug> Neo4gBuilder<Q> {
             set_number,
             with_number,
             return_refs,
+            hooked,
         ) = inner_builder_closure(inner_builder).build_inner();
+        self.param_slots.extend(param_slots);
         self.entity_aliases.extend(entity_aliases);
+        self.hooked.extend(hooked);
         self.node_number = node_number;
         self.relation_number = relation_number;
         self.set_number = set_number;
         self.with_number = with_number;
         self.unwind_number = unwind_number;
-        return_refs.iter().map(|i| self.return_refs.insert(i.clone()));
+        return_refs.into_iter().for_each(|i| push_return_ref(&mut self.return_refs, i));
         let aliases: Vec<String> = wrapped_slice.iter().map(|entity| {
             entity.get_alias()
         }).collect();
@@ -312,7 +442,7 @@ impl<Q: CanMatch+Debug> Neo4gBuilder<Q> {
     /// ```
     /// and asociated params.
     pub fn optional_match(mut self) -> Neo4gMatchStatement<Empty> {
-        self.clause = Clause::Match;
+        self.clause = Clause::OptionalMatch;
         if !self.query.is_empty() {
             self.query.push_str("\n");
         }
@@ -345,6 +475,33 @@ impl<Q: CanWith+Debug> Neo4gBuilder<Q> {
     }
 }
 
+impl<Q: Debug> Neo4gBuilder<Q> {
+    /// Appends a raw Cypher fragment, for anything the builder doesn't support yet.
+    /// Placeholders like {user} are replaced with the alias the builder assigned to the referenced entity.
+    /// The params are merged into the builder's params.
+    /// Returns Neo4gError::ParamCollision if a param name is already in use,
+    /// and Neo4gError::UnknownEntity if a referenced entity hasn't been added to the query yet.
+    /// # Example
+    /// ```rust
+    /// .raw(
+    ///     Raw::new("CALL db.index.fulltext.queryNodes('users', $search) YIELD node WHERE node = {user}")
+    ///         .entity("user", &user),
+    ///     HashMap::from([("search".to_string(), "adm*".into())])
+    /// )?
+    /// ```
+    /// The example above generates the following query:
+    /// ```rust
+    /// CALL db.index.fulltext.queryNodes('users', $search) YIELD node WHERE node = user1
+    /// ```
+    /// and asociated params.
+    pub fn raw(mut self, fragment: Raw, params: HashMap<String, BoltType>) -> Result<Neo4gBuilder<Rawed>> {
+        let query_part = fragment.resolve(&self.entity_aliases)?;
+        merge_raw_params(&mut self.params, params)?;
+        push_raw(&mut self.query, &query_part);
+        Ok(self.transition::<Rawed>())
+    }
+}
+
 impl<Q: CanSetWith+Debug> Neo4gBuilder<Q> {
         /// Generates comma separated entity aliases.
         /// # Example
@@ -441,15 +598,23 @@ impl Neo4gBuilder<WithCondition> {
     /// WHERE entity1alias IS NOT NULL AND size(entity2alias) > $co_intdead
     /// ```
     /// and asociated params.
-    pub fn filter(mut self, filter: Where<Condition>) -> Neo4gBuilder<WithConditioned> {
+    /// # Panics
+    /// If a param of a raw condition is already in use, or a condition references an entity that hasn't been added to the query.
+    /// Use try_filter to get these as errors instead.
+    pub fn filter(self, filter: Where<Condition>) -> Neo4gBuilder<WithConditioned> {
+        self.try_filter(filter).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Generates a WHERE call like filter, but returns Neo4gError::ParamCollision if a param of a raw condition is already in use,
+    /// and Neo4gError::UnknownEntity if a condition references an entity that hasn't been added to the query.
+    pub fn try_filter(mut self, filter: Where<Condition>) -> Result<Neo4gBuilder<WithConditioned>> {
+        check_raw_params(&self.params, &filter)?;
+        let (query_part, uuids, where_params, where_slots) = filter.build();
+        let query_part = resolve_uuids(query_part, &uuids, &self.entity_aliases)?;
+        self.param_slots.extend(param_slots(where_slots, &self.entity_aliases));
         self.query.push_str(" WHERE ");
-        let (mut query_part, uuids, where_params) = filter.build();
-        for u in uuids {
-            query_part = query_part.replace(&u.to_string(), self.entity_aliases.get(&u).unwrap_or(&"WTF?".to_owned()));
-        }
         self.query.push_str(&query_part);
         self.params.extend(where_params);
-        self.transition::<WithConditioned>()
+        Ok(self.transition::<WithConditioned>())
     }
 }
 
@@ -466,6 +631,7 @@ impl<Q: CanNode+Debug> Neo4gCreateStatement<Q> {
     /// (nodealias:NodeLabel {prop1: $node1_prop1, prop2: $node1_prop2, propn: $node1_propn})
     /// ```
     /// and asociated params.
+    /// Fields marked #[neo4g(created_at)] or #[neo4g(updated_at)] are set to the database time instead of a param.
     pub fn node<T: Neo4gEntity>(mut self, entity: &mut T) -> Neo4gCreateStatement<CreatedNode>
     { //where EntityWrapper: From<T>, T: Clone {
         self.node_number += 1;
@@ -482,7 +648,10 @@ impl<Q: CanNode+Debug> Neo4gCreateStatement<Q> {
         }
         self.previous_entity = Some((alias.clone(), EntityType::Node));
         self.entity_aliases.insert(entity.get_uuid(), alias);
-        let (query_part, params) = entity.create_from_self();
+        entity.ensure_id();
+        hook_once(&mut self.hooked, entity);
+        let (query_part, mut params) = entity.create_from_self();
+        let query_part = timestamps::server_side_pattern(entity, &query_part, &mut params);
         self.query.push_str(&query_part);
         self.params.extend(params);
         self.transition::<CreatedNode>()
@@ -532,7 +701,10 @@ impl Neo4gCreateStatement<CreatedNode> {
         let name = format!("{}:{}", &alias, &label);
         self.previous_entity = Some((alias.clone(), EntityType::Relation));
         self.entity_aliases.insert(entity.get_uuid(), alias);
-        let (query_part, params) = entity.create_from_self();
+        entity.ensure_id();
+        hook_once(&mut self.hooked, entity);
+        let (query_part, mut params) = entity.create_from_self();
+        let query_part = timestamps::server_side_pattern(entity, &query_part, &mut params);
         self.query.push_str(&query_part);
         self.params.extend(params);
         self.transition::<CreatedRelation>()
@@ -567,18 +739,27 @@ impl Neo4gCreateStatement<CreatedNode> {
         self
     }
 }
+impl<Q: Debug> Neo4gCreateStatement<Q> {
+    /// Appends a raw Cypher fragment to the statement, on a new line. See Neo4gBuilder::raw for details.
+    pub fn raw(mut self, fragment: Raw, params: HashMap<String, BoltType>) -> Result<Self> {
+        let query_part = fragment.resolve(&self.entity_aliases)?;
+        merge_raw_params(&mut self.params, params)?;
+        push_raw(&mut self.query, &query_part);
+        Ok(self)
+    }
+}
 impl <Q: CanAddReturn> Neo4gCreateStatement<Q> {
     pub fn add_to_return(mut self) -> Self {
         if let Some((alias, entity_type)) = self.previous_entity.clone() {
-            self.return_refs.insert((alias, entity_type));
+            push_return_ref(&mut self.return_refs, (alias, entity_type));
         }
         self
     }
 }
-impl <Q: PossibleStatementEnd> Neo4gCreateStatement<Q> {
+impl <Q: PossibleStatementEnd+Debug> Neo4gCreateStatement<Q> {
     /// Finalises the current statement, tidies up placeholders, and changes the state of the builder so that new statements can be added.
     pub fn end_statement(mut self) -> Neo4gBuilder<CreatedNode> {
-        self.query = self.query.replace(":AdditionalLabels", "");
+        self.query = self.statement_query();
         Neo4gBuilder::from(self)
     }
 }
@@ -596,9 +777,11 @@ impl<Q: CanNode+Debug> Neo4gMergeStatement<Q> {
     /// (nodealias:NodeLabel {prop1: $node1_prop1, prop2: $node1_prop2})
     /// ```
     /// and asociated params.
+    /// Fields marked #[neo4g(created_at)] are set to the database time ON CREATE, and fields marked #[neo4g(updated_at)] on both ON CREATE and ON MATCH.
     pub fn node<T, F>(mut self, entity: &mut T, props_macro: F) -> Neo4gMergeStatement<CreatedNode>
     where T: Neo4gEntity, T::Props: Clone, F: FnOnce(&T) -> Vec<T::Props> {
         self.node_number += 1;
+        hook_once(&mut self.hooked, entity);
         let props = props_macro(entity);
         let label = entity.get_label();
         let mut alias = format!("{}{}", label.to_lowercase(), self.node_number);
@@ -617,9 +800,10 @@ impl<Q: CanNode+Debug> Neo4gMergeStatement<Q> {
             self.query.push_str(&format!("({})", name));
         } else {
             let (query_part, params) = entity.entity_by(&alias, &props);
-            self.query.push_str(&query_part);
+            self.query.push_str(&query_part.replacen(&format!("({}:{}", &alias, &label), &format!("({}", name), 1));
             self.params.extend(params);
         }
+        self.set_managed_timestamps(entity, &alias);
         self.entity_aliases.insert(entity.get_uuid(), alias);
         self.transition::<CreatedNode>()
     }
@@ -636,6 +820,7 @@ impl<Q: CanNode+Debug> Neo4gMergeStatement<Q> {
     pub fn nodes_by_unwound<T, F, A>(mut self, entity: &mut T, prop_macro: F, unwound: &Unwinder) ->  Neo4gMergeStatement<CreatedNode>
     where T: Neo4gEntity, T::Props: Clone, F: FnOnce(&T) -> T::Props {
         self.node_number += 1;
+        hook_once(&mut self.hooked, entity);
         let prop = prop_macro(entity);
         let mut alias = entity.get_alias();
         if alias.is_empty() {
@@ -653,7 +838,7 @@ impl<Q: CanNode+Debug> Neo4gMergeStatement<Q> {
         }
         let (prop_name, _) = prop.to_query_param();
         self.query.push_str(&format!("({}{{{}: {}}})", name, prop_name, unwound_alias));
-        
+        self.set_managed_timestamps(entity, &alias);
         self.transition::<CreatedNode>()
     }
     /// Provides a node alias for use in a query string. 
@@ -671,6 +856,21 @@ impl<Q: CanNode+Debug> Neo4gMergeStatement<Q> {
         self.query.push_str(&format!("({})",node_ref));
         self.transition::<CreatedNode>()
     }
+    /// Generates a node query object, using the key props marked with #[neo4g(key)] as the conditions for the MERGE.
+    /// Only nodes with a key implement KeyedNode, so an unconstrained MERGE can't be built.
+    /// # Example
+    /// ```rust
+    /// .node_by_key(&mut user)
+    /// ```
+    /// The example above generates the following query:
+    /// ```rust
+    /// (user1:User {id: $user1_id})
+    /// ```
+    /// and asociated params.
+    pub fn node_by_key<T>(self, entity: &mut T) -> Neo4gMergeStatement<CreatedNode>
+    where T: KeyedNode, T::Props: Clone {
+        self.node(entity, |entity: &T| entity.key_props())
+    }
 }
 impl Neo4gMergeStatement<CreatedNode> {
     /// Generates a relation query object with a minimum number of relations traversed. 
@@ -687,6 +887,7 @@ impl Neo4gMergeStatement<CreatedNode> {
     pub fn relations<T, F>(mut self, min_hops: u32, entity: &mut T, props_macro: F) -> Neo4gMergeStatement<CreatedRelation>
     where T: Neo4gEntity, T::Props: Clone, F: FnOnce(&T) -> Vec<T::Props> {
         self.relation_number += 1;
+        hook_once(&mut self.hooked, entity);
         let props = props_macro(entity);
         let label = entity.get_label();
         let mut alias = format!("{}{}", label.to_lowercase(), self.relation_number);
@@ -704,6 +905,7 @@ impl Neo4gMergeStatement<CreatedNode> {
         let (query_part, params) = entity.entity_by(&alias, &props);
         self.query.push_str(&query_part.replace("min_hops", &format!("{}", min_hops)));
         self.params.extend(params);
+        self.set_managed_timestamps(entity, &alias);
         self.entity_aliases.insert(entity.get_uuid(), alias);
         self.transition::<CreatedRelation>()
     }
@@ -721,6 +923,7 @@ impl Neo4gMergeStatement<CreatedNode> {
     pub fn relation<T, F>(mut self, entity: &mut T, props_macro: F) -> Neo4gMergeStatement<CreatedRelation>
     where T: Neo4gEntity, T::Props: Clone, F: FnOnce(&T) -> Vec<T::Props> {
         self.relation_number += 1;
+        hook_once(&mut self.hooked, entity);
         let props = props_macro(entity);
         let label = entity.get_label();
         let mut alias = format!("{}{}", label.to_lowercase(), self.relation_number);
@@ -738,6 +941,7 @@ impl Neo4gMergeStatement<CreatedNode> {
         let (query_part, params) = entity.entity_by(&alias, &props);
         self.query.push_str(&query_part.replace("*min_hops..", ""));
         self.params.extend(params);
+        self.set_managed_timestamps(entity, &alias);
         self.entity_aliases.insert(entity.get_uuid(), alias);
         self.transition::<CreatedRelation>()
     }
@@ -755,6 +959,7 @@ impl Neo4gMergeStatement<CreatedNode> {
     pub fn relation_flipped<T, F>(mut self, entity: &mut T, props_macro: F) -> Neo4gMergeStatement<CreatedRelation>
     where T: Neo4gEntity, T::Props: Clone, F: FnOnce(&T) -> Vec<T::Props> {
         self.relation_number += 1;
+        hook_once(&mut self.hooked, entity);
         let props = props_macro(entity);
         self.relation_number += 1;
         let label = entity.get_label();
@@ -773,6 +978,7 @@ impl Neo4gMergeStatement<CreatedNode> {
         let (query_part, params) = entity.entity_by(&alias,&props);
         self.query.push_str(&query_part.replace("-[", "<-[").replace("]->", "]-"));
         self.params.extend(params);
+        self.set_managed_timestamps(entity, &alias);
         self.entity_aliases.insert(entity.get_uuid(), alias);
         self.transition::<CreatedRelation>()
     }
@@ -810,15 +1016,24 @@ impl Neo4gMergeStatement<CreatedNode> {
         self
     }
 }
+impl<Q: Debug> Neo4gMergeStatement<Q> {
+    /// Appends a raw Cypher fragment to the statement, on a new line. See Neo4gBuilder::raw for details.
+    pub fn raw(mut self, fragment: Raw, params: HashMap<String, BoltType>) -> Result<Self> {
+        let query_part = fragment.resolve(&self.entity_aliases)?;
+        merge_raw_params(&mut self.params, params)?;
+        push_raw(&mut self.query, &query_part);
+        Ok(self)
+    }
+}
 impl <Q: CanAddReturn> Neo4gMergeStatement<Q> {
     pub fn add_to_return(mut self) -> Self {
         if let Some((alias, entity_type)) = self.previous_entity.clone() {
-            self.return_refs.insert((alias, entity_type));
+            push_return_ref(&mut self.return_refs, (alias, entity_type));
         }
         self
     }
 }
-impl <Q: PossibleStatementEnd> Neo4gMergeStatement<Q> {
+impl <Q: PossibleStatementEnd+Debug> Neo4gMergeStatement<Q> {
     /// Appends ON CREATE to the query string and changes the builder state so that .set() can be called
     /// # Example
     /// ```rust
@@ -854,10 +1069,18 @@ impl <Q: PossibleStatementEnd> Neo4gMergeStatement<Q> {
     /// SET entity1alias.prop1 = $set1_prop1, entity1alias.prop2 = $set1_prop2, entity2alias.prop1 = $set2_prop1, entity2alias.prop2 = $set2_prop2
     /// ```
     /// and asociated params for the inner builder.
+    /// NOTE: Versions are not checked here. Only .set() in a MATCH statement guards fields marked #[neo4g(version)],
+    /// so use Neo4gRepository::save to merge a versioned node.
     pub fn set<T, F>(mut self, entity: &T, props_macro: F) -> Self
     where T: Neo4gEntity, T::Props: Clone, F: FnOnce(&T) -> Vec<T::Props> {
         //where T::Props: Clone, PropsWrapper: From<<T as Neo4gEntity>::Props> {
         self.set_number += 1;
+        // Entities created or merged earlier in the query were hooked in place, so only the others are hooked here.
+        let mut saved = entity.clone();
+        if !self.hooked.contains(&saved.get_uuid()) {
+            saved.hook_before_save();
+        }
+        let entity = &saved;
         let props = props_macro(entity);
         let alias = entity.get_alias();
         let mut query = String::new();
@@ -866,8 +1089,10 @@ impl <Q: PossibleStatementEnd> Neo4gMergeStatement<Q> {
             .iter()
             .map(|prop| {
                 let (key, value) = prop.to_query_param();
-                params.insert(format!("set_{}{}", key.to_string(), self.set_number), value);
-                format!("{}.{} = $set_{}{}", alias, key, key, self.set_number)
+                let param = format!("set_{}{}", key, self.set_number);
+                self.param_slots.insert(param.clone(), ParamSlot { prop: key.to_string(), alias: Some(alias.clone()) });
+                params.insert(param.clone(), value);
+                format!("{}.{} = ${}", alias, key, param)
             })
             .collect();
 
@@ -896,9 +1121,7 @@ impl <Q: PossibleStatementEnd> Neo4gMergeStatement<Q> {
     }
     /// Finalises the current statement, tidies up placeholders, and changes the state of the builder so that new statements can be added.
     pub fn end_statement(mut self) -> Neo4gBuilder<CreatedNode> {
-        self.query = self.query.replace(":AdditionalLabels", "");
-        //// println!("INSIDE MERGE! Query: {}", &self.query);
-        self.query.push_str(&format!("{}{}", self.on_match_str, self.on_create_str));
+        self.query = self.statement_query();
         Neo4gBuilder::from(self)
     }
 }
@@ -931,13 +1154,14 @@ impl<Q: CanNode+Debug> Neo4gMatchStatement<Q> {
         } else {
             entity.set_alias(&alias);
         }
+        self.filter_deleted(entity, &alias, &props);
         let name = format!("{}:{}:AdditionalLabels", &alias, &label);
         self.previous_entity = Some((alias.clone(), EntityType::Node));
         if props.is_empty() {
             self.query.push_str(&format!("({})", name));
         } else {
             let (query_part, params) = entity.entity_by(&alias, &props);
-            self.query.push_str(&query_part);
+            self.query.push_str(&query_part.replacen(&format!("({}:{}", &alias, &label), &format!("({}", name), 1));
             self.params.extend(params);
         }
         self.entity_aliases.insert(entity.get_uuid(), alias);
@@ -972,6 +1196,7 @@ impl<Q: CanNode+Debug> Neo4gMatchStatement<Q> {
             unwound_alias = self.entity_aliases.get(&unwound_uuid).unwrap().into();
         }
         let (prop_name, _) = prop.to_query_param();
+        self.filter_deleted(entity, &alias, &[prop]);
         self.query.push_str(&format!("({}{{{}: {}}})", name, prop_name, unwound_alias));
         
         self.transition::<MatchedNode>()
@@ -991,6 +1216,21 @@ impl<Q: CanNode+Debug> Neo4gMatchStatement<Q> {
         self.query.push_str(&format!("({})",node_ref));
         self.transition::<MatchedNode>()
     }
+    /// Generates a node query object, using the key props marked with #[neo4g(key)] as the conditions for the MATCH.
+    /// Only nodes with a key implement KeyedNode, so an unconstrained MATCH can't be built.
+    /// # Example
+    /// ```rust
+    /// .node_by_key(&mut user)
+    /// ```
+    /// The example above generates the following query:
+    /// ```rust
+    /// (user1:User {id: $user1_id})
+    /// ```
+    /// and asociated params.
+    pub fn node_by_key<T>(self, entity: &mut T) -> Neo4gMatchStatement<MatchedNode>
+    where T: KeyedNode, T::Props: Clone {
+        self.node(entity, |entity: &T| entity.key_props())
+    }
 }
 impl Neo4gMatchStatement<MatchedNode> {
     /// Generates a relation query object with a minimum number of relations traversed. 
@@ -1053,6 +1293,7 @@ impl Neo4gMatchStatement<MatchedNode> {
         } else {
             entity.set_alias(&alias);
         }
+        self.filter_deleted(entity, &alias, &props);
         let name = format!("{}:{}", &alias, &label);
         self.previous_entity = Some((alias.clone(), EntityType::Relation));
         let (query_part, params) = entity.entity_by(&alias, &props);
@@ -1129,10 +1370,55 @@ impl Neo4gMatchStatement<MatchedNode> {
         self
     }
 }
+impl<Q: Debug> Neo4gMatchStatement<Q> {
+    /// Matches soft deleted entities as well. It applies to every entity in the statement, wherever it is called.
+    /// By default, entities with a field marked #[neo4g(soft_delete)] only match when it is false or missing,
+    /// as the statement's WHERE clause includes coalesce(alias.deleted, false) = false for each of them.
+    /// # Example
+    /// ```rust
+    /// Neo4gBuilder::new()
+    ///     .get()
+    ///     .with_deleted()
+    ///     .node(&mut user, props!(user => user.name))
+    /// ```
+    pub fn with_deleted(mut self) -> Self {
+        self.deleted_filter = DeletedFilter::Include;
+        self
+    }
+    /// Matches only soft deleted entities. Like with_deleted, it applies to every entity in the statement.
+    pub fn only_deleted(mut self) -> Self {
+        self.deleted_filter = DeletedFilter::Only;
+        self
+    }
+    /// Records an entity's soft_delete field, unless its props already include it.
+    /// The conditions are only written by end_statement, so the statement's deleted filter applies to all of them.
+    fn filter_deleted<T: Neo4gEntity>(&mut self, entity: &T, alias: &str, props: &[T::Props]) {
+        if let Some(prop) = entity.soft_delete_prop(false) {
+            let key = prop.to_query_param().0;
+            let soft_delete = (alias.to_string(), key);
+            if !props.iter().any(|existing| existing.to_query_param().0 == key) && !self.soft_deletes.contains(&soft_delete) {
+                self.soft_deletes.push(soft_delete);
+            }
+        }
+    }
+    /// Appends a raw Cypher fragment to the statement, on a new line. See Neo4gBuilder::raw for details.
+    /// The soft delete conditions are added after the fragment, so a condition belongs in filter, using Where::raw.
+    pub fn raw(mut self, fragment: Raw, params: HashMap<String, BoltType>) -> Result<Self> {
+        let query_part = fragment.resolve(&self.entity_aliases)?;
+        merge_raw_params(&mut self.params, params)?;
+        push_raw(&mut self.query, &query_part);
+        Ok(self)
+    }
+}
 impl <Q: CanAddReturn> Neo4gMatchStatement<Q> {
+    /// Adds the previous entity to the RETURN clause.
+    /// Entities from an OPTIONAL MATCH may be null in a row, and are passed to unpack as DbEntityWrapper::Null.
     pub fn add_to_return(mut self) -> Self {
         if let Some((alias, entity_type)) = self.previous_entity.clone() {
-            self.return_refs.insert((alias, entity_type));
+            if let Clause::OptionalMatch = self.clause {
+                self.optional_refs.insert(alias.clone());
+            }
+            push_return_ref(&mut self.return_refs, (alias, entity_type));
         }
         self
     }
@@ -1152,25 +1438,32 @@ impl <Q: PossibleStatementEnd+Debug> Neo4gMatchStatement<Q> {
     /// WHERE paramable1alias_or_fncall > $co_intdead AND entity2alias IN arrayalias
     /// ```
     /// and asociated params for the inner builder.
-    pub fn filter(mut self, filter: Where<Condition>) -> Self {
-        let mut first_where_call = true;
-        if self.where_str.is_empty() {
-            self.where_str.push_str("\nWHERE ")
+    /// # Panics
+    /// If a param of a raw condition is already in use, or a condition references an entity that hasn't been added to the query.
+    /// Use try_filter to get these as errors instead.
+    pub fn filter(self, filter: Where<Condition>) -> Self {
+        self.try_filter(filter).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Generates a WHERE call like filter, but returns Neo4gError::ParamCollision if a param of a raw condition is already in use,
+    /// and Neo4gError::UnknownEntity if a condition references an entity that hasn't been added to the query.
+    pub fn try_filter(mut self, filter: Where<Condition>) -> Result<Self> {
+        check_raw_params(&self.params, &filter)?;
+        let (query_part, uuids, where_params, where_slots) = filter.build();
+        let query_part = resolve_uuids(query_part, &uuids, &self.entity_aliases)?;
+        self.param_slots.extend(param_slots(where_slots, &self.entity_aliases));
+        // Conditions are kept together at the WHERE clause, even when other clauses were appended after it,
+        // so the soft delete conditions can be put in front of all of them.
+        let at = *self.where_at.get_or_insert(self.query.len());
+        let where_str = if self.where_str.is_empty() {
+            format!("\nWHERE {}", query_part)
         } else {
-            first_where_call = false;
-        }
-        let (mut query_part, uuids, where_params) = filter.build();
-        for u in uuids {
-            query_part = query_part.replace(&u.to_string(), self.entity_aliases.get(&u).unwrap_or(&"WTF?".to_owned()));
-        }
-        self.where_str.push_str(&query_part);
-        if first_where_call {
-            self.query.push_str(&self.where_str);
-        } else {
-            self.query.push_str(&query_part);
-        }
+            // Each filter is grouped, so that its conditions are all required, whatever joiners they use.
+            format!("\nWHERE ({}) AND ({})", &self.where_str["\nWHERE ".len()..], query_part)
+        };
+        self.query.replace_range(at..at + self.where_str.len(), &where_str);
+        self.where_str = where_str;
         self.params.extend(where_params);
-        self
+        Ok(self)
     }
     /// Generates a SET call
     /// # Example
@@ -1183,21 +1476,50 @@ impl <Q: PossibleStatementEnd+Debug> Neo4gMatchStatement<Q> {
     /// SET entity1alias.prop1 = $set1_prop1, entity1alias.prop2 = $set1_prop2, entity2alias.prop1 = $set2_prop1, entity2alias.prop2 = $set2_prop2
     /// ```
     /// and asociated params for the inner builder.
+    /// Entities with a field marked #[neo4g(updated_at)] also have it set to the database time.
+    /// Entities with a field marked #[neo4g(version)] are only set if it still holds the version they were read at, and it is incremented.
+    /// When no rows match because the version changed, running the query returns Neo4gError::ConcurrentModification,
+    /// whether it is run, streamed or prepared.
+    /// NOTE: The entity passed in keeps the version it was read at, so setting it again in a later query fails.
+    /// Add it to the return and set the entity that comes back instead, which holds the new version.
     pub fn set<T, F>(mut self, entity: &T, props_macro: F) -> Self
     where T: Neo4gEntity, T::Props: Clone, F: FnOnce(&T) -> Vec<T::Props> {
         self.set_number += 1;
+        // Entities created or merged earlier in the query were hooked in place, so only the others are hooked here.
+        let mut saved = entity.clone();
+        if !self.hooked.contains(&saved.get_uuid()) {
+            saved.hook_before_save();
+        }
+        let entity = &saved;
         let props = props_macro(entity);
         let alias = entity.get_alias();
         let mut query = String::new();
         let mut params = std::collections::HashMap::new();
-        let props_str: Vec<String> = props
+        let mut props_str: Vec<String> = props
             .iter()
             .map(|prop| {
                 let (key, value) = prop.to_query_param();
-                params.insert(format!("set_{}{}", key.to_string(), self.set_number), value);
-                format!("{}.{} = $set_{}{}", alias, key, key, self.set_number)
+                let param = format!("set_{}{}", key, self.set_number);
+                self.param_slots.insert(param.clone(), ParamSlot { prop: key.to_string(), alias: Some(alias.clone()) });
+                params.insert(param.clone(), value);
+                format!("{}.{} = ${}", alias, key, param)
             })
             .collect();
+        // The updated_at prop is set by the database, unless it is one of the props being set.
+        let updated = entity.updated_at_prop().map(|(prop, _)| prop);
+        if !props.iter().any(|prop| Some(prop.to_query_param().0) == updated) {
+            props_str.extend(timestamps::on_write_sets(entity, &alias));
+        }
+        // Versioned entities are only set if their version is still the one they were read at, and the version is bumped.
+        if let Some(version) = entity.version_prop() {
+            let (key, value) = version.to_query_param();
+            let param = format!("{}{}", VERSION_PARAM_PREFIX, alias);
+            if !self.params.contains_key(&param) {
+                self.version_guards.push(format!("{}.{} = ${}", alias, key, param));
+                self.params.insert(param, value);
+                props_str.push(format!("{}.{} = {}.{} + 1", alias, key, alias, key));
+            }
+        }
         query.push_str(&props_str.join(", "));
         self.params.extend(params);
         if self.set_str.is_empty() {
@@ -1209,34 +1531,44 @@ impl <Q: PossibleStatementEnd+Debug> Neo4gMatchStatement<Q> {
         self
     }
     /// Adds DELETE entity1alias, entity2alias to the query.
+    /// Entities with a field marked #[neo4g(soft_delete)] are soft deleted instead, by adding SET entityalias.deleted = true to the SET call.
     pub fn delete<T: WrappedNeo4gEntity>(mut self, entities: &[T], detach: bool) -> Neo4gMatchStatement<DeletedEntity>{
-        let aliases = entities.iter().map(|e| {
+        let mut aliases = Vec::new();
+        let mut soft_delete_sets = Vec::new();
+        for e in entities {
+            e.hook_before_delete();
             let mut alias = e.get_alias();
             if alias.is_empty() {
                 let uuid = e.get_uuid();
                 alias = self.entity_aliases.get(&uuid).unwrap().to_owned();
             }
-            alias
-        }).collect::<Vec<String>>();
-        let detach_string = if detach {"DETACH "} else {""};
-        self.query.push_str(&format!("\nDELETE {}{}", detach_string, aliases.join(", ")));
+            let sets = e.soft_delete_sets(&alias);
+            if sets.is_empty() {
+                aliases.push(alias);
+            } else {
+                soft_delete_sets.extend(sets);
+            }
+        }
+        if !soft_delete_sets.is_empty() {
+            if self.set_str.is_empty() {
+                self.set_str = "\nSET ".to_string();
+            } else {
+                self.set_str.push_str(", ");
+            }
+            self.set_str.push_str(&soft_delete_sets.join(", "));
+        }
+        if !aliases.is_empty() {
+            if self.where_at.is_none() {
+                self.where_at = Some(self.query.len());
+            }
+            let detach_string = if detach {"DETACH "} else {""};
+            self.query.push_str(&format!("\nDELETE {}{}", detach_string, aliases.join(", ")));
+        }
         self.transition::<DeletedEntity>()
     }
     /// Finalises the current statement, tidies up placeholders, and changes the state of the builder so that new statements can be added.
     pub fn end_statement(mut self) -> Neo4gBuilder<MatchedNode> {
-        // if !self.where_str.is_empty() {
-        //     self.query.push_str(&format!("{}", self.where_str));
-        // }
-        if !self.set_str.is_empty() {
-            self.query.push_str(&format!("{}", self.set_str));
-            if !self.return_refs.is_empty() {
-                let return_aliases: Vec<String> = self.return_refs.iter().map(|item| {
-                    item.0.clone()
-                }).collect();
-                self.query.push_str(&format!("\nWITH {}", return_aliases.join(", ")));
-            }
-        }
-        self.query = self.query.replace(":AdditionalLabels", "");
+        self.query = self.statement_query();
         Neo4gBuilder::from(self)
     }
 }
@@ -1339,6 +1671,75 @@ impl <Q: PossibleQueryEnd+Debug> Neo4gBuilder<Q> {
         self.unioned = true;
         self.transition::<WithConditioned>()
     }
+    /// Finalises the query into a PreparedQuery, which can be run repeatedly with new param values bound to it.
+    /// The RETURN and ORDER BY clauses are appended here, so the query text is only assembled once.
+    /// # Example
+    /// ```rust
+    /// let mut prepared = Neo4gBuilder::new()
+    ///     .get()
+    ///         .node(&mut user, props!(user => user.name)).add_to_return()
+    ///     .end_statement()
+    ///     .prepare();
+    /// prepared.bind(UserProps::Name("admin".into()))?;
+    /// let result = prepared.run_query(graph, EntityWrapper::from_db_entity).await;
+    /// ```
+    pub fn prepare(self) -> PreparedQuery {
+        let query = self.final_query();
+        let versioned = self.is_versioned();
+        let return_refs = self.return_refs;
+        let entity_aliases = self.entity_aliases.into_values().collect();
+        PreparedQuery::new(query, self.params, self.param_slots, return_refs, self.optional_refs, entity_aliases, versioned)
+    }
+    /// Returns the finished query, indented with one clause per line. The builder is left untouched.
+    /// # Example
+    /// ```rust
+    /// println!("{}", builder.to_cypher_pretty());
+    /// ```
+    /// prints:
+    /// ```rust
+    /// MATCH (user1:User {name: $user1_name})
+    ///   WHERE user1.deleted = $co_deleted3f2a
+    /// RETURN user1
+    /// ```
+    pub fn to_cypher_pretty(&self) -> String {
+        pretty_cypher(&self.final_query())
+    }
+    /// Returns a script of :param lines followed by the finished query, ready to paste into Neo4j Browser or cypher-shell.
+    /// # Example
+    /// ```rust
+    /// println!("{}", builder.to_browser_script());
+    /// ```
+    /// prints:
+    /// ```rust
+    /// :param user1_name => 'admin'
+    /// MATCH (user1:User {name: $user1_name})
+    /// RETURN user1;
+    /// ```
+    pub fn to_browser_script(&self) -> String {
+        browser_script(&self.final_query(), &self.params)
+    }
+    /// Returns the finished query and its params in a normalized form that is stable between runs, for snapshot tests.
+    /// # Example
+    /// ```rust
+    /// assert_cypher_snapshot("tests/snapshots/get_user_by_name.cypher", &builder.render());
+    /// ```
+    pub fn render(&self) -> Rendered {
+        Rendered::new(&self.final_query(), &self.params)
+    }
+    /// The query with the RETURN and ORDER BY clauses appended.
+    fn final_query(&self) -> String {
+        let mut query = self.query.clone();
+        if !self.return_refs.is_empty() {
+            query.push_str("\nRETURN ");
+            let aliases: Vec<&str> = self.return_refs.iter().map(|(alias, _)| alias.as_str()).collect();
+            query.push_str(&aliases.join(", "));
+        } else if self.is_versioned() {
+            // Versioned writes need a row back to show that the version matched.
+            query.push_str("\nRETURN true AS version_matched");
+        }
+        query.push_str(&self.order_by_str);
+        query
+    }
     /// Runs the query against a provided Graph and returns the registered return objects in nested Vecs.
     /// The outer Vec contains Vecs that represent rows. The inner Vec contains wrapped entities within each row.
     /// # Example:
@@ -1355,52 +1756,9 @@ impl <Q: PossibleQueryEnd+Debug> Neo4gBuilder<Q> {
     /// ```rust
     /// .execute_in_txn(txn, EntityWrapper::from_db_entity).await;
     /// ```
-    pub async fn execute_in_txn<F, R>(mut self, txn: &mut Txn, unpack: F) -> Result<Vec<Vec<F::Output>>>
+    pub async fn execute_in_txn<F, R>(self, txn: &mut Txn, unpack: F) -> Result<Vec<Vec<F::Output>>>
     where F: Fn(DbEntityWrapper) -> R {
-        if !self.return_refs.is_empty() {
-            self.query.push_str("\nRETURN ");
-            let aliases: Vec<&str> = self.return_refs.iter().map(|(alias, _)| alias.as_str()).collect();
-            self.query.push_str(&aliases.join(", "));
-        }
-        self.query.push_str(&self.order_by_str);
-        //println!("query: {}", self.query.clone());
-        //println!("params: {:?}", self.params.clone());
-        let query = Query::new(self.query).params(self.params);
-        let mut return_vec: Vec<Vec<R>> = Vec::new();
-        let mut result = txn.execute(query).await?;
-            //Ok(mut result) => {
-        println!("query ran");
-        while let Ok(Some(row)) = result.next(txn.handle()).await {
-            let mut row_vec: Vec<R> = Vec::new();
-            for (alias, entity_type) in &self.return_refs {
-                match entity_type {
-                    EntityType::Node => {
-                        if let Ok(node) = row.get::<Node>(&alias) {
-                            let wrapped_entity = unpack(DbEntityWrapper::Node(node));
-                            row_vec.push(wrapped_entity);
-                        } else {
-                            return Err(Neo4gError::NodeNotFound { alias: alias.clone() });
-                        }
-                    },
-                    EntityType::Relation => {
-                        if let Ok(relation) = row.get::<Relation>(&alias) {
-                            let wrapped_entity = unpack(DbEntityWrapper::Relation(relation));
-                            row_vec.push(wrapped_entity);
-                        } else {
-                            return Err(Neo4gError::RelationNotFound { alias: alias.clone() });
-                        }
-                    },
-                    _ => {
-                        return Err(Neo4gError::UnexpectedError { alias: alias.clone() });
-                    }
-                }
-            }
-            return_vec.push(row_vec);
-        }
-            //},
-            //Err(e) => return Err(anyhow!("Query didn't run. Error: {}", e)),
-        //}
-        Ok(return_vec)
+        self.run_with(txn, unpack).await
     }
     /// Runs the query against a provided Graph and returns the registered return objects in nested Vecs.
     /// The outer Vec contains Vecs that represent rows. The inner Vec contains wrapped entities within each row.
@@ -1408,49 +1766,88 @@ impl <Q: PossibleQueryEnd+Debug> Neo4gBuilder<Q> {
     /// ```rust
     /// .run_query(graph, EntityWrapper::from_db_entity).await;
     /// ```
-    pub async fn run_query<F, R>(mut self, graph: Graph, unpack: F) -> Result<Vec<Vec<F::Output>>>
+    pub async fn run_query<F, R>(self, mut graph: Graph, unpack: F) -> Result<Vec<Vec<F::Output>>>
     where F: Fn(DbEntityWrapper) -> R {
-        if !self.return_refs.is_empty() {
-            self.query.push_str("\nRETURN ");
-            let aliases: Vec<&str> = self.return_refs.iter().map(|(alias, _)| alias.as_str()).collect();
-            self.query.push_str(&aliases.join(", "));
+        self.run_with(&mut graph, unpack).await
+    }
+    /// Runs the query against a provided Graph and returns a stream of rows, decoded lazily as the stream is polled.
+    /// Use this instead of run_query for large results, so rows don't all have to be held in memory.
+    /// # Example:
+    /// ```rust
+    /// let mut rows = std::pin::pin!(builder.stream(graph, EntityWrapper::from_db_entity));
+    /// while let Some(row) = rows.next().await {
+    ///     let row = row?;
+    /// }
+    /// ```
+    pub fn stream<F, R>(self, graph: Graph, unpack: F) -> impl Stream<Item = Result<Vec<R>>>
+    where F: Fn(DbEntityWrapper) -> R {
+        let query = self.final_query();
+        let versioned = self.is_versioned();
+        let return_refs = self.return_refs;
+        stream(graph, query, self.params, return_refs, self.optional_refs, versioned, unpack)
+    }
+    /// Runs the query against any Executor and returns the registered return objects in nested Vecs.
+    /// run_query and execute_in_txn are shorthands for this with a Graph and a Txn.
+    /// # Example:
+    /// ```rust
+    /// .run_with(&mut recording_executor, EntityWrapper::from_db_entity).await;
+    /// ```
+    /// Returns Neo4gError::ConcurrentModification if the query sets a versioned entity and no rows match.
+    pub async fn run_with<E, F, R>(self, executor: &mut E, unpack: F) -> Result<Vec<Vec<F::Output>>>
+    where E: Executor, F: Fn(DbEntityWrapper) -> R {
+        let query = self.final_query();
+        let versioned = self.is_versioned();
+        let return_refs = self.return_refs;
+        let rows = execute(executor, query.clone(), self.params, &return_refs, &self.optional_refs, unpack).await?;
+        if versioned && rows.is_empty() {
+            return Err(Neo4gError::ConcurrentModification { query });
         }
-        self.query.push_str(&self.order_by_str);
-        //println!("query: {}", self.query.clone());
-        //println!("params: {:?}", self.params.clone());
-        let query = Query::new(self.query).params(self.params);
-        let mut return_vec: Vec<Vec<R>> = Vec::new();
-        let mut result = graph.execute(query).await?;
-        println!("query ran");
-        while let Ok(Some(row)) = result.next().await {
-            let mut row_vec: Vec<R> = Vec::new();
-            for (alias, entity_type) in &self.return_refs {
-                match entity_type {
-                    EntityType::Node => {
-                        if let Ok(node) = row.get::<Node>(&alias) {
-                            let wrapped_entity = unpack(DbEntityWrapper::Node(node));
-                            row_vec.push(wrapped_entity);
-                        } else {
-                            return Err(Neo4gError::NodeNotFound { alias: alias.clone() });
-                        }
-                    },
-                    EntityType::Relation => {
-                        if let Ok(relation) = row.get::<Relation>(&alias) {
-                            let wrapped_entity = unpack(DbEntityWrapper::Relation(relation));
-                            row_vec.push(wrapped_entity);
-                        } else {
-                            return Err(Neo4gError::RelationNotFound { alias: alias.clone() });
-                        }
-                    },
-                    _ => {
-                        return Err(Neo4gError::UnexpectedError { alias: alias.clone() });
-                    }
+        Ok(rows)
+    }
+    /// Whether the query sets a versioned entity, which is checked against the version it was read at.
+    fn is_versioned(&self) -> bool {
+        self.params.keys().any(|key| key.starts_with(VERSION_PARAM_PREFIX))
+    }
+}
+
+/// Registers a return ref once, keeping the order in which entities were added so the RETURN clause is stable.
+fn push_return_ref(return_refs: &mut Vec<(String, EntityType)>, item: (String, EntityType)) {
+    if !return_refs.contains(&item) {
+        return_refs.push(item);
+    }
+}
+
+/// Unpacks the registered return objects from a single row, in the order of return_refs.
+/// Aliases in optional_refs that are null in the row are unpacked as DbEntityWrapper::Null instead of failing the row.
+pub(crate) fn decode_row<'a, F, R, I>(row: &Row, return_refs: I, optional_refs: &HashSet<String>, unpack: &F) -> Result<Vec<R>>
+where F: Fn(DbEntityWrapper) -> R, I: IntoIterator<Item = &'a (String, EntityType)> {
+    let mut row_vec: Vec<R> = Vec::new();
+    for (alias, entity_type) in return_refs {
+        if optional_refs.contains(alias) && matches!(row.get::<BoltType>(alias), Ok(BoltType::Null(_))) {
+            row_vec.push(unpack(DbEntityWrapper::Null));
+            continue;
+        }
+        match entity_type {
+            EntityType::Node => {
+                if let Ok(node) = row.get::<Node>(alias) {
+                    row_vec.push(unpack(DbEntityWrapper::Node(node)));
+                } else {
+                    return Err(Neo4gError::NodeNotFound { alias: alias.clone() });
+                }
+            },
+            EntityType::Relation => {
+                if let Ok(relation) = row.get::<Relation>(alias) {
+                    row_vec.push(unpack(DbEntityWrapper::Relation(relation)));
+                } else {
+                    return Err(Neo4gError::RelationNotFound { alias: alias.clone() });
                 }
+            },
+            _ => {
+                return Err(Neo4gError::UnexpectedError { alias: alias.clone() });
             }
-            return_vec.push(row_vec);
         }
-        Ok(return_vec)
     }
+    Ok(row_vec)
 }
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
@@ -1462,11 +1859,32 @@ pub enum EntityType {
     Array,
 }
 
+/// Which soft deleted entities a match statement matches. See Neo4gMatchStatement::with_deleted.
+#[derive(Clone, Debug, Default)]
+pub enum DeletedFilter {
+    #[default]
+    Exclude,
+    Include,
+    Only,
+}
+
+impl DeletedFilter {
+    /// The WHERE condition on an entity's soft_delete field. Nodes written before the field existed have no value, so they count as not deleted.
+    pub(crate) fn condition(&self, alias: &str, key: &str) -> Option<String> {
+        match self {
+            DeletedFilter::Exclude => Some(format!("coalesce({}.{}, false) = false", alias, key)),
+            DeletedFilter::Only => Some(format!("{}.{} = true", alias, key)),
+            DeletedFilter::Include => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Clause {
     Create,
     Merge,
     Match,
+    OptionalMatch,
     Delete,
     None,
 }
@@ -1475,6 +1893,7 @@ pub enum Clause {
 pub struct Neo4gMatchStatement<State> {
     query: String,
     params: HashMap<String, BoltType>,
+    param_slots: HashMap<String, ParamSlot>,
     entity_aliases: HashMap<Uuid, String>,
     node_number: u32,
     relation_number: u32,
@@ -1483,9 +1902,15 @@ pub struct Neo4gMatchStatement<State> {
     with_number: u32,
     where_str: String,
     set_str: String,
-    return_refs: HashSet<(String, EntityType)>,
+    return_refs: Vec<(String, EntityType)>,
+    optional_refs: HashSet<String>,
+    hooked: HashSet<Uuid>,
     previous_entity: Option<(String, EntityType)>,
     clause: Clause,
+    deleted_filter: DeletedFilter,
+    soft_deletes: Vec<(String, &'static str)>,
+    where_at: Option<usize>,
+    version_guards: Vec<String>,
     unioned: bool,
     _state: PhantomData<State>,
 }
@@ -1494,6 +1919,7 @@ pub struct Neo4gMatchStatement<State> {
 pub struct Neo4gMergeStatement<State> {
     query: String,
     params: HashMap<String, BoltType>,
+    param_slots: HashMap<String, ParamSlot>,
     entity_aliases: HashMap<Uuid, String>,
     node_number: u32,
     relation_number: u32,
@@ -1503,7 +1929,9 @@ pub struct Neo4gMergeStatement<State> {
     on_create_str: String,
     on_match_str: String,
     current_on_str: OnString,
-    return_refs: HashSet<(String, EntityType)>,
+    return_refs: Vec<(String, EntityType)>,
+    optional_refs: HashSet<String>,
+    hooked: HashSet<Uuid>,
     previous_entity: Option<(String, EntityType)>,
     clause: Clause,
     unioned: bool,
@@ -1514,13 +1942,16 @@ pub struct Neo4gMergeStatement<State> {
 pub struct Neo4gCreateStatement<State> {
     query: String,
     params: HashMap<String, BoltType>,
+    param_slots: HashMap<String, ParamSlot>,
     entity_aliases: HashMap<Uuid, String>,
     node_number: u32,
     relation_number: u32,
     unwind_number: u32,
     set_number: u32,
     with_number: u32,
-    return_refs: HashSet<(String, EntityType)>,
+    return_refs: Vec<(String, EntityType)>,
+    optional_refs: HashSet<String>,
+    hooked: HashSet<Uuid>,
     previous_entity: Option<(String, EntityType)>,
     clause: Clause,
     unioned: bool,
@@ -1533,6 +1964,7 @@ impl<S: Debug> Neo4gBuilder<S> {
         let Neo4gBuilder {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1540,6 +1972,8 @@ impl<S: Debug> Neo4gBuilder<S> {
             set_number,
             with_number,
             return_refs,
+            optional_refs,
+            hooked,
             order_by_str,
             previous_entity,
             clause,
@@ -1549,6 +1983,7 @@ impl<S: Debug> Neo4gBuilder<S> {
         Neo4gBuilder {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1556,6 +1991,8 @@ impl<S: Debug> Neo4gBuilder<S> {
             set_number,
             with_number,
             return_refs,
+            optional_refs,
+            hooked,
             order_by_str,
             previous_entity,
             clause,
@@ -1563,8 +2000,8 @@ impl<S: Debug> Neo4gBuilder<S> {
             _state: std::marker::PhantomData,
         }
     }
-    fn build_inner(self) -> (String, HashMap<String, BoltType>, HashMap<Uuid, String>, u32, u32, u32, u32, u32, HashSet<(String, EntityType)>) {
-        (self.query, self.params, self.entity_aliases, self.node_number, self.relation_number, self.unwind_number, self.set_number, self.with_number, self.return_refs)
+    fn build_inner(self) -> (String, HashMap<String, BoltType>, HashMap<String, ParamSlot>, HashMap<Uuid, String>, u32, u32, u32, u32, u32, Vec<(String, EntityType)>, HashSet<Uuid>) {
+        (self.query, self.params, self.param_slots, self.entity_aliases, self.node_number, self.relation_number, self.unwind_number, self.set_number, self.with_number, self.return_refs, self.hooked)
     }
     pub fn debug(self) {
         dbg!(&self);
@@ -1577,6 +2014,7 @@ impl<S: Debug> Neo4gMatchStatement<S> {
         let Neo4gMatchStatement {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1586,14 +2024,21 @@ impl<S: Debug> Neo4gMatchStatement<S> {
             where_str,
             set_str,
             return_refs,
+            optional_refs,
+            hooked,
             previous_entity,
             clause,
+            deleted_filter,
+            soft_deletes,
+            where_at,
+            version_guards,
             unioned,
             ..
         } = self;
         Neo4gMatchStatement {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1603,8 +2048,14 @@ impl<S: Debug> Neo4gMatchStatement<S> {
             set_str,
             with_number,
             return_refs,
+            optional_refs,
+            hooked,
             previous_entity,
             clause,
+            deleted_filter,
+            soft_deletes,
+            where_at,
+            version_guards,
             unioned,
             _state: std::marker::PhantomData,
         }
@@ -1612,14 +2063,59 @@ impl<S: Debug> Neo4gMatchStatement<S> {
     pub fn debug(self) {
         dbg!(&self);
     }
+    /// The query as .end_statement() leaves it: the soft delete conditions are added to the WHERE clause,
+    /// the version guards and SET clause are appended and placeholders are removed.
+    fn statement_query(&self) -> String {
+        let mut query = self.query.clone();
+        let deleted: Vec<String> = self.soft_deletes.iter().filter_map(|(alias, key)| self.deleted_filter.condition(alias, key)).collect();
+        if !deleted.is_empty() {
+            let conditions = deleted.join(" AND ");
+            let at = self.where_at.unwrap_or(query.len());
+            if self.where_str.is_empty() {
+                query.insert_str(at, &format!("\nWHERE {}", conditions));
+            } else {
+                // The filter's conditions are grouped, so an OR among them can't bypass the soft delete conditions.
+                query.insert_str(at + self.where_str.len(), ")");
+                query.insert_str(at + "\nWHERE ".len(), &format!("{} AND (", conditions));
+            }
+        }
+        if !self.version_guards.is_empty() {
+            query.push_str(&format!("\nWITH *\nWHERE {}", self.version_guards.join(" AND ")));
+        }
+        if !self.set_str.is_empty() {
+            query.push_str(&self.set_str);
+            if !self.return_refs.is_empty() {
+                let return_aliases: Vec<String> = self.return_refs.iter().map(|item| {
+                    item.0.clone()
+                }).collect();
+                query.push_str(&format!("\nWITH {}", return_aliases.join(", ")));
+            }
+        }
+        query.replace(":AdditionalLabels", "")
+    }
+    /// Returns the query built so far, as .end_statement() would leave it, indented with one clause per line.
+    pub fn to_cypher_pretty(&self) -> String {
+        pretty_cypher(&self.statement_query())
+    }
+    /// Returns a script of :param lines followed by the query built so far, as .end_statement() would leave it,
+    /// ready to paste into Neo4j Browser or cypher-shell.
+    pub fn to_browser_script(&self) -> String {
+        browser_script(&self.statement_query(), &self.params)
+    }
 }
 
 impl<S: Debug> Neo4gMergeStatement<S> {
+    /// Sets the managed created_at and updated_at props of a merged entity ON CREATE, and its updated_at prop ON MATCH.
+    fn set_managed_timestamps<T: Neo4gEntity>(&mut self, entity: &T, alias: &str) {
+        push_on_set(&mut self.on_create_str, "\nON CREATE", timestamps::on_create_sets(entity, alias));
+        push_on_set(&mut self.on_match_str, "\nON MATCH", timestamps::on_write_sets(entity, alias));
+    }
     /// Consumes self and returns a new builder with the marker type changed to NewState.
     fn transition<NewState>(self) -> Neo4gMergeStatement<NewState> {
         let Neo4gMergeStatement {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1630,6 +2126,8 @@ impl<S: Debug> Neo4gMergeStatement<S> {
             on_match_str,
             current_on_str,
             return_refs,
+            optional_refs,
+            hooked,
             previous_entity,
             clause,
             unioned,
@@ -1638,6 +2136,7 @@ impl<S: Debug> Neo4gMergeStatement<S> {
         Neo4gMergeStatement {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1648,6 +2147,8 @@ impl<S: Debug> Neo4gMergeStatement<S> {
             on_match_str,
             current_on_str,
             return_refs,
+            optional_refs,
+            hooked,
             previous_entity,
             clause,
             unioned,
@@ -1657,6 +2158,21 @@ impl<S: Debug> Neo4gMergeStatement<S> {
     pub fn debug(self) {
         dbg!(&self);
     }
+    /// The query as .end_statement() leaves it: the ON MATCH and ON CREATE clauses are appended and placeholders are removed.
+    fn statement_query(&self) -> String {
+        let mut query = self.query.replace(":AdditionalLabels", "");
+        query.push_str(&format!("{}{}", self.on_match_str, self.on_create_str));
+        query
+    }
+    /// Returns the query built so far, as .end_statement() would leave it, indented with one clause per line.
+    pub fn to_cypher_pretty(&self) -> String {
+        pretty_cypher(&self.statement_query())
+    }
+    /// Returns a script of :param lines followed by the query built so far, as .end_statement() would leave it,
+    /// ready to paste into Neo4j Browser or cypher-shell.
+    pub fn to_browser_script(&self) -> String {
+        browser_script(&self.statement_query(), &self.params)
+    }
 }
 
 impl<S: Debug> Neo4gCreateStatement<S> {
@@ -1665,6 +2181,7 @@ impl<S: Debug> Neo4gCreateStatement<S> {
         let Neo4gCreateStatement {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1672,6 +2189,8 @@ impl<S: Debug> Neo4gCreateStatement<S> {
             set_number,
             with_number,
             return_refs,
+            optional_refs,
+            hooked,
             previous_entity,
             clause,
             unioned,
@@ -1680,6 +2199,7 @@ impl<S: Debug> Neo4gCreateStatement<S> {
         Neo4gCreateStatement {
             query,
             params,
+            param_slots,
             entity_aliases,
             node_number,
             relation_number,
@@ -1687,6 +2207,8 @@ impl<S: Debug> Neo4gCreateStatement<S> {
             set_number,
             with_number,
             return_refs,
+            optional_refs,
+            hooked,
             previous_entity,
             clause,
             unioned,
@@ -1696,6 +2218,19 @@ impl<S: Debug> Neo4gCreateStatement<S> {
     pub fn debug(self) {
         dbg!(&self);
     }
+    /// The query as .end_statement() leaves it, with placeholders removed.
+    fn statement_query(&self) -> String {
+        self.query.replace(":AdditionalLabels", "")
+    }
+    /// Returns the query built so far, as .end_statement() would leave it, indented with one clause per line.
+    pub fn to_cypher_pretty(&self) -> String {
+        pretty_cypher(&self.statement_query())
+    }
+    /// Returns a script of :param lines followed by the query built so far, as .end_statement() would leave it,
+    /// ready to paste into Neo4j Browser or cypher-shell.
+    pub fn to_browser_script(&self) -> String {
+        browser_script(&self.statement_query(), &self.params)
+    }
 }
 
 impl<S> From<Neo4gBuilder<S>> for Neo4gCreateStatement<Empty> {
@@ -1703,6 +2238,7 @@ impl<S> From<Neo4gBuilder<S>> for Neo4gCreateStatement<Empty> {
         Neo4gCreateStatement::<Empty> {
             query: value.query,
             params: value.params,
+            param_slots: value.param_slots,
             entity_aliases: value.entity_aliases,
             node_number: value.node_number,
             relation_number: value.relation_number,
@@ -1710,6 +2246,8 @@ impl<S> From<Neo4gBuilder<S>> for Neo4gCreateStatement<Empty> {
             set_number: value.set_number,
             with_number: value.with_number,
             return_refs: value.return_refs,
+            optional_refs: value.optional_refs,
+            hooked: value.hooked,
             previous_entity: value.previous_entity,
             clause: value.clause,
             unioned: value.unioned,
@@ -1723,6 +2261,7 @@ impl<S> From<Neo4gBuilder<S>> for Neo4gMergeStatement<Empty> {
         Neo4gMergeStatement::<Empty> {
             query: value.query,
             params: value.params,
+            param_slots: value.param_slots,
             entity_aliases: value.entity_aliases,
             node_number: value.node_number,
             relation_number: value.relation_number,
@@ -1733,6 +2272,8 @@ impl<S> From<Neo4gBuilder<S>> for Neo4gMergeStatement<Empty> {
             on_match_str: "".to_string(),
             current_on_str: OnString::None,
             return_refs: value.return_refs,
+            optional_refs: value.optional_refs,
+            hooked: value.hooked,
             previous_entity: value.previous_entity,
             clause: value.clause,
             unioned: value.unioned,
@@ -1746,6 +2287,7 @@ impl<S> From<Neo4gBuilder<S>> for Neo4gMatchStatement<Empty> {
         Neo4gMatchStatement::<Empty> {
             query: value.query,
             params: value.params,
+            param_slots: value.param_slots,
             entity_aliases: value.entity_aliases,
             node_number: value.node_number,
             relation_number: value.relation_number,
@@ -1755,8 +2297,14 @@ impl<S> From<Neo4gBuilder<S>> for Neo4gMatchStatement<Empty> {
             where_str: String::new(),
             set_str: String::new(),
             return_refs: value.return_refs,
+            optional_refs: value.optional_refs,
+            hooked: value.hooked,
             previous_entity: value.previous_entity,
             clause: value.clause,
+            deleted_filter: DeletedFilter::Exclude,
+            soft_deletes: Vec::new(),
+            where_at: None,
+            version_guards: Vec::new(),
             unioned: value.unioned,
             _state: std::marker::PhantomData,
         }
@@ -1768,6 +2316,7 @@ impl<S> From<Neo4gMatchStatement<S>> for Neo4gBuilder<MatchedNode> {
         Neo4gBuilder::<MatchedNode> {
             query: value.query,
             params: value.params,
+            param_slots: value.param_slots,
             entity_aliases: value.entity_aliases,
             node_number: value.node_number,
             relation_number: value.relation_number,
@@ -1775,6 +2324,8 @@ impl<S> From<Neo4gMatchStatement<S>> for Neo4gBuilder<MatchedNode> {
             set_number: value.set_number,
             with_number: value.with_number,
             return_refs: value.return_refs,
+            optional_refs: value.optional_refs,
+            hooked: value.hooked,
             order_by_str: String::new(),
             previous_entity: value.previous_entity,
             clause: value.clause,
@@ -1789,6 +2340,7 @@ impl<S> From<Neo4gMergeStatement<S>> for Neo4gBuilder<CreatedNode> {
         Neo4gBuilder::<CreatedNode> {
             query: value.query,
             params: value.params,
+            param_slots: value.param_slots,
             entity_aliases: value.entity_aliases,
             node_number: value.node_number,
             relation_number: value.relation_number,
@@ -1796,6 +2348,8 @@ impl<S> From<Neo4gMergeStatement<S>> for Neo4gBuilder<CreatedNode> {
             set_number: value.set_number,
             with_number: value.with_number,
             return_refs: value.return_refs,
+            optional_refs: value.optional_refs,
+            hooked: value.hooked,
             order_by_str: String::new(),
             previous_entity: value.previous_entity,
             clause: value.clause,
@@ -1810,6 +2364,7 @@ impl<S> From<Neo4gCreateStatement<S>> for Neo4gBuilder<CreatedNode> {
         Neo4gBuilder::<CreatedNode> {
             query: value.query,
             params: value.params,
+            param_slots: value.param_slots,
             entity_aliases: value.entity_aliases,
             node_number: value.node_number,
             relation_number: value.relation_number,
@@ -1817,6 +2372,8 @@ impl<S> From<Neo4gCreateStatement<S>> for Neo4gBuilder<CreatedNode> {
             set_number: value.set_number,
             with_number: value.with_number,
             return_refs: value.return_refs,
+            optional_refs: value.optional_refs,
+            hooked: value.hooked,
             order_by_str: String::new(),
             previous_entity: value.previous_entity,
             clause: value.clause,
@@ -1922,22 +2479,138 @@ impl Aliasable for Array {
     }
 }
 
+/// A raw Cypher fragment with {name} placeholders that resolve to the aliases of referenced entities.
+#[derive(Debug, Clone)]
+pub struct Raw {
+    fragment: String,
+    refs: Vec<(String, String, Uuid)>,
+}
+
+impl Raw {
+    /// Creates a raw fragment.
+    pub fn new(fragment: &str) -> Self {
+        Self {
+            fragment: fragment.to_string(),
+            refs: Vec::new(),
+        }
+    }
+    /// Registers an entity for the {name} placeholder.
+    pub fn entity<A: Aliasable>(mut self, name: &str, aliasable: &A) -> Self {
+        self.refs.push((name.to_string(), aliasable.get_alias(), aliasable.get_uuid()));
+        self
+    }
+    /// Replaces placeholders with known aliases, or with uuids to be replaced once the builder knows the alias.
+    fn to_query_uuid(&self) -> (String, Vec<Uuid>) {
+        let mut query = self.fragment.clone();
+        let mut uuids = Vec::new();
+        for (name, alias, uuid) in &self.refs {
+            let placeholder = format!("{{{}}}", name);
+            if alias.is_empty() {
+                query = query.replace(&placeholder, &uuid.to_string());
+                uuids.push(uuid.clone());
+            } else {
+                query = query.replace(&placeholder, alias);
+            }
+        }
+        (query, uuids)
+    }
+    /// Replaces placeholders with aliases, failing if a referenced entity hasn't been aliased by the builder.
+    fn resolve(&self, entity_aliases: &HashMap<Uuid, String>) -> Result<String> {
+        let (query, uuids) = self.to_query_uuid();
+        resolve_uuids(query, &uuids, entity_aliases).map_err(|_| Neo4gError::UnknownEntity { part: self.fragment.clone() })
+    }
+}
+
+/// Runs the BeforeSave hook of an entity in place, unless it has already run on that entity in this query.
+fn hook_once<T: Neo4gEntity>(hooked: &mut HashSet<Uuid>, entity: &mut T) {
+    if hooked.insert(entity.get_uuid()) {
+        entity.hook_before_save();
+    }
+}
+
+/// Appends SET items to an ON CREATE or ON MATCH string, starting it with the given header if it is empty.
+fn push_on_set(on_str: &mut String, header: &str, items: Vec<String>) {
+    if items.is_empty() {
+        return;
+    }
+    if on_str.is_empty() {
+        on_str.push_str(header);
+    }
+    if on_str == header {
+        on_str.push_str("\nSET ");
+    } else {
+        on_str.push_str(", ");
+    }
+    on_str.push_str(&items.join(", "));
+}
+
+/// Merges the params of a raw fragment, failing without merging any if a param name is already in use.
+/// Resolves the entities that the params of a filter were compared with to their aliases.
+fn param_slots(where_slots: HashMap<String, (String, Option<Uuid>)>, entity_aliases: &HashMap<Uuid, String>) -> impl Iterator<Item = (String, ParamSlot)> + '_ {
+    where_slots.into_iter().map(|(param, (prop, uuid))| {
+        let alias = uuid.and_then(|uuid| entity_aliases.get(&uuid).cloned());
+        (param, ParamSlot { prop, alias })
+    })
+}
+
+fn merge_raw_params(existing: &mut HashMap<String, BoltType>, params: HashMap<String, BoltType>) -> Result<()> {
+    if let Some(param) = params.keys().find(|key| existing.contains_key(*key)) {
+        return Err(Neo4gError::ParamCollision { param: param.clone() });
+    }
+    existing.extend(params);
+    Ok(())
+}
+
+/// Appends a resolved raw fragment on a new line, unless the query already ends with whitespace.
+fn push_raw(query: &mut String, query_part: &str) {
+    if !query.is_empty() && !query.ends_with(char::is_whitespace) {
+        query.push('\n');
+    }
+    query.push_str(query_part);
+}
+
+/// Replaces the uuid placeholders in a query part with the aliases the builder assigned, failing on an entity it doesn't know.
+fn resolve_uuids(mut query_part: String, uuids: &[Uuid], entity_aliases: &HashMap<Uuid, String>) -> Result<String> {
+    for u in uuids {
+        match entity_aliases.get(u) {
+            Some(alias) => query_part = query_part.replace(&u.to_string(), alias),
+            None => return Err(Neo4gError::UnknownEntity { part: query_part }),
+        }
+    }
+    Ok(query_part)
+}
+
+/// Fails if a raw condition param of a filter is already in use by the query, or by another condition of the filter.
+fn check_raw_params(existing: &HashMap<String, BoltType>, filter: &Where<Condition>) -> Result<()> {
+    if let Some(param) = filter.raw_collisions.first() {
+        return Err(Neo4gError::ParamCollision { param: param.clone() });
+    }
+    if let Some(param) = filter.raw_params.iter().find(|param| existing.contains_key(*param)) {
+        return Err(Neo4gError::ParamCollision { param: param.clone() });
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct Where<State> {
     string: String,
     params: HashMap<String, BoltType>,
+    // The prop each by_prop param holds, and the uuid of the entity it is compared with, if any.
+    param_slots: HashMap<String, (String, Option<Uuid>)>,
     uuids: Vec<Uuid>,
     condition_number: u32,
+    raw_params: Vec<String>,
+    raw_collisions: Vec<String>,
     _state: PhantomData<State>,
 }
 
 impl<S> Where<S> {
     fn transition<NewState>(self) -> Where<NewState> {
-        let Where {string, params, uuids, condition_number, ..} = self;
-        Where {string, params, uuids, condition_number, _state: std::marker::PhantomData,}
+        let Where {string, params, param_slots, uuids, condition_number, raw_params, raw_collisions, ..} = self;
+        Where {string, params, param_slots, uuids, condition_number, raw_params, raw_collisions, _state: std::marker::PhantomData,}
     }
-    fn build_inner(self) -> (String, HashMap<String, BoltType>, Vec<Uuid>, u32) {
-        (self.string, self.params, self.uuids, self.condition_number)
+    fn build_inner(self) -> (String, HashMap<String, BoltType>, HashMap<String, (String, Option<Uuid>)>, Vec<Uuid>, u32, Vec<String>, Vec<String>) {
+        (self.string, self.params, self.param_slots, self.uuids, self.condition_number, self.raw_params, self.raw_collisions)
     }
     pub fn debug() {
         todo!()
@@ -1950,8 +2623,11 @@ impl Where<Empty> {
         Self {
             string: String::new(),
             params: HashMap::new(),
+            param_slots: HashMap::new(),
             uuids: Vec::new(),
             condition_number: 0,
+            raw_params: Vec::new(),
+            raw_collisions: Vec::new(),
             _state: PhantomData,
         }
     }
@@ -1959,8 +2635,11 @@ impl Where<Empty> {
         Self {
             string: String::new(),
             params: HashMap::new(),
+            param_slots: HashMap::new(),
             uuids: Vec::new(),
             condition_number: parent.condition_number,
+            raw_params: Vec::new(),
+            raw_collisions: Vec::new(),
             _state: PhantomData,
         }
     }
@@ -1990,6 +2669,7 @@ impl<Q: CanCondition> Where<Q> {
         self.condition_number += 1;
         let (op_string, op_uuids, op_params) = operator.to_query_uuid_param();
         self.params.extend(op_params);
+        self.param_slots.extend(operator.param_props.into_iter().map(|(param, prop)| (param, (prop, None))));
         self.uuids.extend_from_slice(&op_uuids);
         let (mut p_string, p_uuids, p_params) = paramable.to_query_uuid_param();
         if p_string.is_empty() && p_uuids.len() == 1 {
@@ -2029,6 +2709,7 @@ impl<Q: CanCondition> Where<Q> {
             entity_alias = entity_uuid.to_string();
         }
         self.params.extend(params);
+        self.param_slots.extend(operator.param_props.into_iter().map(|(param, prop)| (param, (prop, Some(entity.get_uuid())))));
         self.uuids.extend_from_slice(&uuids);
         self.string.push_str(&format!("{}{} {}", entity_alias, prop_name, string));
         self.transition::<Condition>()
@@ -2070,13 +2751,42 @@ impl<Q: CanCondition> Where<Q> {
         let inner_builder = Where::new_with_parent(&self);
         let (query,
             params,
+            param_slots,
             uuids,
-            condition_number
+            condition_number,
+            raw_params,
+            raw_collisions
         ) = inner_builder_closure(inner_builder).build_inner();
         self.condition_number = condition_number;
+        self.raw_collisions.extend(raw_collisions);
+        self.raw_collisions.extend(raw_params.iter().filter(|param| self.params.contains_key(*param)).cloned());
+        self.raw_params.extend(raw_params);
         self.uuids.extend_from_slice(&uuids);
         self.string.push_str(&format!("({})", query));
         self.params.extend(params);
+        self.param_slots.extend(param_slots);
+        self.transition::<Condition>()
+    }
+    /// Appends a raw Cypher condition. Placeholders like {user} are resolved to aliases when the filter is applied.
+    /// Applying the filter with try_filter returns Neo4gError::ParamCollision if a param name is already in use by the filter or the query,
+    /// and filter panics.
+    /// # Example
+    /// ```rust
+    /// .raw(Raw::new("{user}.name =~ $pattern").entity("user", &user), HashMap::from([("pattern".to_string(), "ad.*".into())]))
+    /// ```
+    /// The example above generates "user1.name =~ $pattern"
+    pub fn raw(mut self, fragment: Raw, params: HashMap<String, BoltType>) -> Where<Condition> {
+        self.condition_number += 1;
+        let (query_part, uuids) = fragment.to_query_uuid();
+        for (key, value) in params {
+            if self.params.contains_key(&key) {
+                self.raw_collisions.push(key.clone());
+            }
+            self.raw_params.push(key.clone());
+            self.params.insert(key, value);
+        }
+        self.uuids.extend_from_slice(&uuids);
+        self.string.push_str(&query_part);
         self.transition::<Condition>()
     }
 }
@@ -2095,8 +2805,8 @@ impl<Q: CanJoin> Where<Q> {
 
 impl Where<Condition> {
     /// Builds the filter and params. This is used by .filter(), and should otherwise not be used unless you know what you're doing. 
-    fn build(self) -> (String, Vec<Uuid>, HashMap<String, BoltType>) {
-        (self.string, self.uuids, self.params)
+    fn build(self) -> (String, Vec<Uuid>, HashMap<String, BoltType>, HashMap<String, (String, Option<Uuid>)>) {
+        (self.string, self.uuids, self.params, self.param_slots)
     }
 }
 
@@ -2115,6 +2825,8 @@ pub enum RefType {
 pub struct CompareOperator {
     query: String,
     params: HashMap<String, BoltType>,
+    // The prop each param holds, so prepared queries can rebind it.
+    param_props: HashMap<String, String>,
     uuids: Vec<Uuid>,
 }
 
@@ -2137,6 +2849,7 @@ impl CompareOperator {
                 Self {
                     query: format!("{} entity_alias.{}", operator, query),
                     params: HashMap::new(),
+                    param_props: HashMap::new(),
                     uuids: Vec::new(),
                 }
             },
@@ -2146,7 +2859,8 @@ impl CompareOperator {
                 let param_name = format!("co_{}{}", query, rng_chars);
                 Self {
                     query: format!("{} ${}", operator, param_name),
-                    params: HashMap::from([(param_name, bolt)]),
+                    params: HashMap::from([(param_name.clone(), bolt)]),
+                    param_props: HashMap::from([(param_name, query.to_string())]),
                     uuids: Vec::new(),
                 }
             }
@@ -2176,6 +2890,7 @@ impl CompareOperator {
         Self {
             query: format!("{} {}", operator, alias),
             params: HashMap::new(),
+            param_props: HashMap::new(),
             uuids,
         }
     }
@@ -2221,6 +2936,37 @@ pub enum CompareJoiner {
 pub enum DbEntityWrapper {
     Node(Node),
     Relation(Relation),
+    /// An entity from an OPTIONAL MATCH that had no match in the row.
+    Null,
+}
+
+impl DbEntityWrapper {
+    pub fn is_null(&self) -> bool {
+        matches!(self, DbEntityWrapper::Null)
+    }
+}
+
+/// Wraps an unpack function so null OPTIONAL MATCH results come back as None, and everything else as Some.
+/// # Example:
+/// ```rust
+/// let pages_with_components = Neo4gBuilder::new()
+///     .get()
+///         .node(&mut page, props!(page => page.id)).add_to_return()
+///     .end_statement()
+///     .optional_match()
+///         .node(&mut page, &[]).relations(0, &mut has_component, &[]).node(&mut component, &[]).add_to_return()
+///     .end_statement()
+///     .run_query(graph, optional(EntityWrapper::from_db_entity)).await?;
+/// ```
+pub fn optional<F, R>(unpack: F) -> impl Fn(DbEntityWrapper) -> Option<R>
+where F: Fn(DbEntityWrapper) -> R {
+    move |db_entity| {
+        if db_entity.is_null() {
+            None
+        } else {
+            Some(unpack(db_entity))
+        }
+    }
 }
 
 impl fmt::Display for CompareJoiner {
@@ -2449,4 +3195,87 @@ impl <A: Aliasable> From<&[&A]> for Expr {
             params: HashMap::new(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use neo4rs::{BoltList, Row};
+
+    use super::*;
+
+    fn params() -> HashMap<String, BoltType> {
+        HashMap::from([("b".to_string(), BoltType::from(1)), ("a".to_string(), BoltType::from(2))])
+    }
+
+    fn classify(code: &str, message: &str) -> Option<Neo4gError> {
+        server_error(code, message, "MATCH (n) RETURN n".to_string(), vec!["a".to_string()])
+    }
+
+    #[test]
+    fn constraint_violations_name_the_label_and_property() {
+        let e = classify("Neo.ClientError.Schema.ConstraintValidationFailed", "Node(12) already exists with label `User` and property `id` = 'x'");
+        assert!(matches!(e, Some(Neo4gError::ConstraintViolation { label: Some(label), property: Some(property), query, .. })
+            if label == "User" && property == "id" && query == "MATCH (n) RETURN n"));
+        let e = classify("Neo.ClientError.Schema.ConstraintValidationFailed", "Node(12) already exists with label `User` and properties `forename`, `surname`");
+        assert!(matches!(e, Some(Neo4gError::ConstraintViolation { property: Some(property), .. }) if property == "forename"));
+        let e = classify("Neo.ClientError.Schema.ConstraintValidationFailed", "constraint violated");
+        assert!(matches!(e, Some(Neo4gError::ConstraintViolation { label: None, property: None, .. })));
+    }
+
+    #[test]
+    fn statement_errors_are_syntax_errors() {
+        for code in ["Neo.ClientError.Statement.SyntaxError", "Neo.ClientError.Statement.ParameterMissing", "Neo.ClientError.Statement.TypeError"] {
+            assert!(matches!(classify(code, "bad"), Some(Neo4gError::Syntax { code: found, .. }) if found == code), "{}", code);
+        }
+    }
+
+    #[test]
+    fn transient_errors_can_be_retried() {
+        let e = classify("Neo.TransientError.Transaction.DeadlockDetected", "deadlock").unwrap();
+        assert!(matches!(e, Neo4gError::Transient { .. }));
+        assert!(e.is_transient());
+        // The server reports these as transient, but they are client errors and retrying does not help.
+        assert!(classify("Neo.TransientError.Transaction.Terminated", "terminated").is_none());
+    }
+
+    #[test]
+    fn security_errors_are_auth_errors() {
+        for code in ["Neo.ClientError.Security.Unauthorized", "Neo.ClientError.Security.Forbidden", "Neo.ClientError.Security.TokenExpired"] {
+            assert!(matches!(classify(code, "denied"), Some(Neo4gError::Auth { .. })), "{}", code);
+        }
+    }
+
+    #[test]
+    fn other_server_errors_are_left_alone() {
+        assert!(classify("Neo.DatabaseError.General.UnknownError", "boom").is_none());
+        assert!(classify("Neo.ClientError.Statement.EntityNotFound", "gone").is_none());
+        assert!(classify("Neo.ClientError.Request.Invalid", "bad request").is_none());
+    }
+
+    #[test]
+    fn decode_errors_carry_the_query_and_sorted_param_names() {
+        let row = Row::new(BoltList::from(vec![BoltType::from("count")]), BoltList::from(vec![BoltType::from("many")]));
+        let de_error = row.get::<i64>("count").unwrap_err();
+        let e = Neo4gError::Db(neo4rs::Error::DeserializationError(de_error)).with_query("RETURN count", &params());
+        assert!(matches!(&e, Neo4gError::Decode { param_names, .. } if *param_names == vec!["a", "b"]));
+        assert_eq!(e.query(), Some("RETURN count"));
+        let e = Neo4gError::NodeNotFound { alias: "user1".to_string() }.with_query("RETURN user1", &params());
+        assert!(matches!(e, Neo4gError::Decode { reason, .. } if reason.contains("user1")));
+    }
+
+    #[test]
+    fn auth_failures_and_unrelated_errors() {
+        let e = Neo4gError::Db(neo4rs::Error::AuthenticationError("wrong password".to_string())).with_query("RETURN 1", &params());
+        assert!(matches!(e, Neo4gError::Auth { message, .. } if message == "wrong password"));
+        let e = Neo4gError::Db(neo4rs::Error::ConnectionError).with_query("RETURN 1", &params());
+        assert!(matches!(e, Neo4gError::Db(neo4rs::Error::ConnectionError)));
+        assert!(e.query().is_none());
+        assert!(matches!(Neo4gError::MigrationLocked.with_query("RETURN 1", &params()), Neo4gError::MigrationLocked));
+    }
+
+    #[test]
+    fn backticked_after_needs_the_marker_and_a_closing_backtick() {
+        assert_eq!(backticked_after("with label `User` and", "label `"), Some("User".to_string()));
+        assert_eq!(backticked_after("with label User", "label `"), None);
+        assert_eq!(backticked_after("with label `User", "label `"), None);
+    }
+}
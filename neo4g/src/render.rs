@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use neo4rs::{BoltDuration, BoltInteger, BoltList, BoltType, Row};
+
+/// Clauses that continue the clause above them and are indented one level under it.
+const SUB_CLAUSES: [&str; 8] = ["WHERE", "SET", "ON CREATE", "ON MATCH", "ORDER BY", "SKIP", "LIMIT", "REMOVE"];
+
+/// Returns the query with one clause per line, sub-clauses indented under their clause and CALL subqueries indented by depth.
+pub fn pretty_cypher(query: &str) -> String {
+    let mut depth = 0usize;
+    let mut lines = Vec::new();
+    for line in query.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        if line.starts_with('}') {
+            depth = depth.saturating_sub(1);
+        }
+        let sub_clause = SUB_CLAUSES.iter().any(|clause| line.starts_with(clause));
+        let indent = depth * 2 + if sub_clause { 2 } else { 0 };
+        lines.push(format!("{}{}", " ".repeat(indent), line));
+        if line.ends_with('{') {
+            depth += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+/// Returns a script of :param lines followed by the pretty query, which can be pasted into Neo4j Browser or cypher-shell.
+/// Params are sorted by name so the output is stable.
+pub fn browser_script(query: &str, params: &HashMap<String, BoltType>) -> String {
+    let mut keys: Vec<&String> = params.keys().collect();
+    keys.sort();
+    let mut script: Vec<String> = keys.iter().map(|key| {
+        format!(":param {} => {}", key, to_cypher_literal(&params[*key]))
+    }).collect();
+    script.push(format!("{};", pretty_cypher(query)));
+    script.join("\n")
+}
+
+/// Renders a Bolt value as a Cypher literal.
+/// Values that have no literal form, such as nodes and bytes, render as null with a comment.
+pub fn to_cypher_literal(value: &BoltType) -> String {
+    match value {
+        BoltType::String(s) => quote_string(&s.value),
+        BoltType::Boolean(b) => b.value.to_string(),
+        BoltType::Null(_) => "null".to_string(),
+        BoltType::Integer(i) => i.value.to_string(),
+        BoltType::Float(f) => {
+            if f.value.is_nan() {
+                "0.0/0.0".to_string()
+            } else if f.value.is_infinite() {
+                if f.value > 0.0 { "1.0/0.0".to_string() } else { "-1.0/0.0".to_string() }
+            } else {
+                format!("{:?}", f.value)
+            }
+        },
+        BoltType::List(list) => format!("[{}]", list.value.iter().map(to_cypher_literal).collect::<Vec<String>>().join(", ")),
+        BoltType::Map(map) => {
+            let mut entries: Vec<(&String, &BoltType)> = map.value.iter().map(|(k, v)| (&k.value, v)).collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            format!("{{{}}}", entries.iter().map(|(k, v)| format!("{}: {}", quote_key(k), to_cypher_literal(v))).collect::<Vec<String>>().join(", "))
+        },
+        BoltType::Point2D(p) => format!("point({{srid: {}, x: {:?}, y: {:?}}})", p.sr_id.value, p.x.value, p.y.value),
+        BoltType::Point3D(p) => format!("point({{srid: {}, x: {:?}, y: {:?}, z: {:?}}})", p.sr_id.value, p.x.value, p.y.value, p.z.value),
+        BoltType::Duration(d) => match duration_seconds(d) {
+            Some((seconds, nanoseconds)) => format!("duration({{seconds: {}, nanoseconds: {}}})", seconds, nanoseconds),
+            None => unsupported("duration with months or days"),
+        },
+        BoltType::Date(d) => match NaiveDate::try_from(d) {
+            Ok(date) => format!("date('{}')", date.format("%Y-%m-%d")),
+            Err(_) => unsupported("date"),
+        },
+        BoltType::Time(t) => {
+            let (time, offset): (NaiveTime, FixedOffset) = t.into();
+            format!("time('{}{}')", time.format("%H:%M:%S%.f"), offset)
+        },
+        BoltType::LocalTime(t) => format!("localtime('{}')", NaiveTime::from(t).format("%H:%M:%S%.f")),
+        BoltType::DateTime(dt) => match DateTime::<FixedOffset>::try_from(dt) {
+            Ok(datetime) => format!("datetime('{}')", datetime.to_rfc3339()),
+            Err(_) => unsupported("datetime"),
+        },
+        BoltType::LocalDateTime(dt) => match NaiveDateTime::try_from(dt) {
+            Ok(datetime) => format!("localdatetime('{}')", datetime.format("%Y-%m-%dT%H:%M:%S%.f")),
+            Err(_) => unsupported("localdatetime"),
+        },
+        BoltType::DateTimeZoneId(dt) => match NaiveDateTime::try_from(dt) {
+            Ok(datetime) => format!("datetime('{}[{}]')", datetime.format("%Y-%m-%dT%H:%M:%S%.f"), dt.tz_id()),
+            Err(_) => unsupported("datetime"),
+        },
+        BoltType::Bytes(_) => unsupported("bytes"),
+        BoltType::Node(_) => unsupported("node"),
+        BoltType::Relation(_) | BoltType::UnboundedRelation(_) => unsupported("relation"),
+        BoltType::Path(_) => unsupported("path"),
+    }
+}
+
+/// Returns the seconds and nanoseconds of a duration, or None if it has months or days.
+/// neo4rs keeps the parts of a duration private, and its public conversions fold months and days into seconds,
+/// so the folded seconds are only used when they rebuild the same duration.
+pub(crate) fn duration_seconds(duration: &BoltDuration) -> Option<(i64, i64)> {
+    let row = Row::new(BoltList::from(vec![BoltType::from("duration")]), BoltList::from(vec![BoltType::Duration(duration.clone())]));
+    let (seconds, nanoseconds): (i64, i64) = row.get("duration").ok()?;
+    let rebuilt = BoltDuration::new(BoltInteger::new(0), BoltInteger::new(0), BoltInteger::new(seconds), BoltInteger::new(nanoseconds));
+    (rebuilt == *duration).then_some((seconds, nanoseconds))
+}
+
+fn unsupported(kind: &str) -> String {
+    format!("null /* {} has no Cypher literal */", kind)
+}
+
+fn quote_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'").replace('\n', "\\n").replace('\r', "\\r").replace('\t', "\\t"))
+}
+
+fn quote_key(key: &str) -> String {
+    let plain = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        key.to_string()
+    } else {
+        format!("`{}`", key.replace('`', "``"))
+    }
+}
+
+/// A query and its params in a stable, normalized form, for comparing against checked-in snapshots.
+/// Condition params, whose names end in a random suffix, are renumbered in order of appearance (co_ide10f becomes co_id_1),
+/// and params are sorted by name. Nothing else is touched, so placeholders leaking into the query show up in the snapshot.
+/// Displays as a script of :param lines followed by the pretty query, so snapshot files can be run in cypher-shell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    pub query: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl Rendered {
+    pub fn new(query: &str, params: &HashMap<String, BoltType>) -> Self {
+        let mut query = query.to_string();
+        let mut renames: Vec<(String, String)> = Vec::new();
+        let mut search_from = 0;
+        while let Some(pos) = query[search_from..].find("$co_").map(|p| p + search_from) {
+            let start = pos + 1;
+            let end = query[start..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).map(|e| e + start).unwrap_or(query.len());
+            let name = query[start..end].to_string();
+            search_from = end;
+            if renames.iter().any(|(old, _)| *old == name) || !params.contains_key(&name) || name.len() < 8 {
+                continue;
+            }
+            let (prop, suffix) = name.split_at(name.len() - 4);
+            if suffix.chars().all(|c| c.is_ascii_hexdigit()) {
+                renames.push((name.clone(), format!("{}_{}", prop, renames.len() + 1)));
+            }
+        }
+        // Rename through a placeholder first, so a new name can never be mistaken for an old one.
+        for (i, (old, _)) in renames.iter().enumerate() {
+            query = replace_param(&query, old, &format!("__neo4g_render_{}", i));
+        }
+        for (i, (_, new)) in renames.iter().enumerate() {
+            query = replace_param(&query, &format!("__neo4g_render_{}", i), new);
+        }
+        let mut rendered_params: Vec<(String, String)> = params.iter().map(|(key, value)| {
+            let key = renames.iter().find(|(old, _)| old == key).map(|(_, new)| new.clone()).unwrap_or_else(|| key.clone());
+            (key, to_cypher_literal(value))
+        }).collect();
+        rendered_params.sort();
+        Self {
+            query: pretty_cypher(&query),
+            params: rendered_params,
+        }
+    }
+}
+
+impl std::fmt::Display for Rendered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in &self.params {
+            writeln!(f, ":param {} => {}", key, value)?;
+        }
+        writeln!(f, "{};", self.query)
+    }
+}
+
+/// Replaces whole $name references in a query.
+fn replace_param(query: &str, old: &str, new: &str) -> String {
+    let pattern = format!("${}", old);
+    let mut result = String::with_capacity(query.len());
+    let mut rest = query;
+    while let Some(pos) = rest.find(&pattern) {
+        let after = &rest[pos + pattern.len()..];
+        result.push_str(&rest[..pos]);
+        if after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            result.push_str(&pattern);
+        } else {
+            result.push('$');
+            result.push_str(new);
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Sorts the aliases of RETURN lines that list plain aliases, so queries that only differ in RETURN order compare equal.
+/// RETURN lines with expressions are left as they are.
+pub(crate) fn sort_return_aliases(query: &str) -> String {
+    query.lines().map(|line| {
+        let trimmed = line.trim_start();
+        let Some(list) = trimmed.strip_prefix("RETURN ") else {
+            return line.to_string();
+        };
+        let mut aliases: Vec<&str> = list.split(',').map(|alias| alias.trim()).collect();
+        if !aliases.iter().all(|alias| !alias.is_empty() && alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+            return line.to_string();
+        }
+        aliases.sort();
+        format!("{}RETURN {}", &line[..line.len() - trimmed.len()], aliases.join(", "))
+    }).collect::<Vec<String>>().join("\n")
+}
+
+/// Compares a rendered query against a checked-in .cypher snapshot file, and panics showing both if they differ.
+/// A missing snapshot is written instead, as is every snapshot when the NEO4G_UPDATE_SNAPSHOTS environment variable is set.
+/// # Example
+/// ```rust
+/// assert_cypher_snapshot("tests/snapshots/get_user_by_name.cypher", &builder.render());
+/// ```
+pub fn assert_cypher_snapshot<P: AsRef<Path>>(path: P, rendered: &Rendered) {
+    let path = path.as_ref();
+    let actual = rendered.to_string();
+    if std::env::var_os("NEO4G_UPDATE_SNAPSHOTS").is_some() || !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap_or_else(|e| panic!("failed to create {}: {}", parent.display(), e));
+        }
+        fs::write(path, &actual).unwrap_or_else(|e| panic!("failed to write snapshot {}: {}", path.display(), e));
+        return;
+    }
+    let expected = fs::read_to_string(path).unwrap_or_else(|e| panic!("failed to read snapshot {}: {}", path.display(), e));
+    if expected.replace("\r\n", "\n") != actual {
+        panic!("query does not match snapshot {}\n--- expected\n{}\n--- actual\n{}\nSet NEO4G_UPDATE_SNAPSHOTS=1 to accept the new query.", path.display(), expected, actual);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pretty_cypher_indents_sub_clauses_under_their_clause() {
+        let query = "MATCH (n:User)\nWHERE n.name = $name\nSET n.seen = true\nRETURN n\nORDER BY n.name ASC";
+        assert_eq!(pretty_cypher(query), "MATCH (n:User)\n  WHERE n.name = $name\n  SET n.seen = true\nRETURN n\n  ORDER BY n.name ASC");
+    }
+
+    #[test]
+    fn pretty_cypher_indents_call_subqueries_by_depth() {
+        let query = "MATCH (n)\nCALL {\nWITH n\nMATCH (n)-[r]->(m)\nWHERE m.x = 1\n}\nRETURN n";
+        assert_eq!(pretty_cypher(query), "MATCH (n)\nCALL {\n  WITH n\n  MATCH (n)-[r]->(m)\n    WHERE m.x = 1\n}\nRETURN n");
+    }
+
+    #[test]
+    fn pretty_cypher_drops_blank_lines_and_surrounding_whitespace() {
+        assert_eq!(pretty_cypher("\n  MATCH (n)  \n\n   RETURN n\n"), "MATCH (n)\nRETURN n");
+    }
+
+    #[test]
+    fn durations_of_seconds_are_rendered() {
+        let duration = BoltDuration::new(BoltInteger::new(0), BoltInteger::new(0), BoltInteger::new(-30), BoltInteger::new(500));
+        assert_eq!(to_cypher_literal(&BoltType::Duration(duration)), "duration({seconds: -30, nanoseconds: 500})");
+    }
+
+    #[test]
+    fn durations_with_months_or_days_are_not_rendered_as_seconds() {
+        for (months, days) in [(14, 0), (0, 3)] {
+            let duration = BoltDuration::new(BoltInteger::new(months), BoltInteger::new(days), BoltInteger::new(-30), BoltInteger::new(500));
+            assert_eq!(to_cypher_literal(&BoltType::Duration(duration)), "null /* duration with months or days has no Cypher literal */");
+        }
+    }
+
+    #[test]
+    fn strings_are_quoted_and_escaped() {
+        assert_eq!(to_cypher_literal(&BoltType::from("it's\na \\ test")), "'it\\'s\\na \\\\ test'");
+    }
+
+    #[test]
+    fn browser_script_sorts_params_by_name() {
+        let params = HashMap::from([("b".to_string(), BoltType::from(2)), ("a".to_string(), BoltType::from("x"))]);
+        assert_eq!(browser_script("RETURN $a, $b", &params), ":param a => 'x'\n:param b => 2\nRETURN $a, $b;");
+    }
+
+    #[test]
+    fn sort_return_aliases_only_sorts_plain_alias_lists() {
+        assert_eq!(sort_return_aliases("MATCH (b)\nRETURN b, a"), "MATCH (b)\nRETURN a, b");
+        assert_eq!(sort_return_aliases("MATCH (b)\nRETURN b.x, a"), "MATCH (b)\nRETURN b.x, a");
+    }
+}
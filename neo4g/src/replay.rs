@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use neo4rs::{BoltDuration, BoltInteger, BoltList, BoltMap, BoltNode, BoltRelation, BoltString, BoltType, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::executor::Executor;
+use crate::query_builder::{Neo4gError, Result};
+use crate::render::{duration_seconds, sort_return_aliases};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+const TIME_FORMAT: &str = "%H:%M:%S%.f";
+
+/// A Bolt value in a form that can be written to and read from a recording file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedValue {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    List(Vec<RecordedValue>),
+    Map(BTreeMap<String, RecordedValue>),
+    Node { id: i64, labels: Vec<String>, properties: BTreeMap<String, RecordedValue> },
+    Relation { id: i64, start: i64, end: i64, typ: String, properties: BTreeMap<String, RecordedValue> },
+    Date(String),
+    LocalTime(String),
+    Time { time: String, offset_seconds: i32 },
+    LocalDateTime(String),
+    DateTime(String),
+    DateTimeZoneId { datetime: String, zone: String },
+    /// Recordings only hold seconds and nanoseconds, as neo4rs doesn't expose the months and days of a duration,
+    /// but fixtures written by hand can set all four.
+    Duration {
+        #[serde(default)]
+        months: i64,
+        #[serde(default)]
+        days: i64,
+        seconds: i64,
+        nanoseconds: i64,
+    },
+    /// A value that can't be recorded, such as bytes, a path or a duration with months or days. Replays as null.
+    Unsupported(String),
+}
+
+impl From<&BoltType> for RecordedValue {
+    fn from(value: &BoltType) -> Self {
+        match value {
+            BoltType::Null(_) => RecordedValue::Null,
+            BoltType::Boolean(b) => RecordedValue::Boolean(b.value),
+            BoltType::Integer(i) => RecordedValue::Integer(i.value),
+            BoltType::Float(f) => RecordedValue::Float(f.value),
+            BoltType::String(s) => RecordedValue::String(s.value.clone()),
+            BoltType::List(list) => RecordedValue::List(list.value.iter().map(RecordedValue::from).collect()),
+            BoltType::Map(map) => RecordedValue::Map(record_map(map)),
+            BoltType::Node(node) => RecordedValue::Node {
+                id: node.id.value,
+                labels: node.labels.value.iter().filter_map(|label| match label {
+                    BoltType::String(s) => Some(s.value.clone()),
+                    _ => None,
+                }).collect(),
+                properties: record_map(&node.properties),
+            },
+            BoltType::Relation(relation) => RecordedValue::Relation {
+                id: relation.id.value,
+                start: relation.start_node_id.value,
+                end: relation.end_node_id.value,
+                typ: relation.typ.value.clone(),
+                properties: record_map(&relation.properties),
+            },
+            BoltType::Date(d) => match NaiveDate::try_from(d) {
+                Ok(date) => RecordedValue::Date(date.to_string()),
+                Err(_) => RecordedValue::Unsupported("date".to_string()),
+            },
+            BoltType::LocalTime(t) => RecordedValue::LocalTime(NaiveTime::from(t).format(TIME_FORMAT).to_string()),
+            BoltType::LocalDateTime(dt) => match NaiveDateTime::try_from(dt) {
+                Ok(datetime) => RecordedValue::LocalDateTime(datetime.format(DATETIME_FORMAT).to_string()),
+                Err(_) => RecordedValue::Unsupported("localdatetime".to_string()),
+            },
+            BoltType::DateTime(dt) => match DateTime::<FixedOffset>::try_from(dt) {
+                Ok(datetime) => RecordedValue::DateTime(datetime.to_rfc3339()),
+                Err(_) => RecordedValue::Unsupported("datetime".to_string()),
+            },
+            BoltType::Duration(d) => match duration_seconds(d) {
+                Some((seconds, nanoseconds)) => RecordedValue::Duration { months: 0, days: 0, seconds, nanoseconds },
+                None => RecordedValue::Unsupported("duration with months or days".to_string()),
+            },
+            BoltType::UnboundedRelation(_) => RecordedValue::Unsupported("unbounded relation".to_string()),
+            BoltType::Point2D(_) | BoltType::Point3D(_) => RecordedValue::Unsupported("point".to_string()),
+            BoltType::Bytes(_) => RecordedValue::Unsupported("bytes".to_string()),
+            BoltType::Path(_) => RecordedValue::Unsupported("path".to_string()),
+            BoltType::Time(t) => {
+                let (time, offset): (NaiveTime, FixedOffset) = t.into();
+                RecordedValue::Time { time: time.format(TIME_FORMAT).to_string(), offset_seconds: offset.local_minus_utc() }
+            },
+            BoltType::DateTimeZoneId(dt) => match NaiveDateTime::try_from(dt) {
+                Ok(datetime) => RecordedValue::DateTimeZoneId { datetime: datetime.format(DATETIME_FORMAT).to_string(), zone: dt.tz_id().to_string() },
+                Err(_) => RecordedValue::Unsupported("datetime with zone id".to_string()),
+            },
+        }
+    }
+}
+
+impl From<&RecordedValue> for BoltType {
+    fn from(value: &RecordedValue) -> Self {
+        match value {
+            RecordedValue::Null | RecordedValue::Unsupported(_) => BoltType::Null(neo4rs::BoltNull),
+            RecordedValue::Boolean(b) => (*b).into(),
+            RecordedValue::Integer(i) => (*i).into(),
+            RecordedValue::Float(f) => (*f).into(),
+            RecordedValue::String(s) => s.as_str().into(),
+            RecordedValue::List(list) => {
+                let mut bolt_list = BoltList::new();
+                for item in list {
+                    bolt_list.push(item.into());
+                }
+                BoltType::List(bolt_list)
+            },
+            RecordedValue::Map(map) => BoltType::Map(replay_map(map)),
+            RecordedValue::Node { id, labels, properties } => {
+                let mut bolt_labels = BoltList::new();
+                for label in labels {
+                    bolt_labels.push(label.as_str().into());
+                }
+                BoltType::Node(BoltNode { id: BoltInteger::new(*id), labels: bolt_labels, properties: replay_map(properties) })
+            },
+            RecordedValue::Relation { id, start, end, typ, properties } => BoltType::Relation(BoltRelation {
+                id: BoltInteger::new(*id),
+                start_node_id: BoltInteger::new(*start),
+                end_node_id: BoltInteger::new(*end),
+                typ: BoltString::new(typ),
+                properties: replay_map(properties),
+            }),
+            RecordedValue::Date(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d").map(BoltType::from).unwrap_or(BoltType::Null(neo4rs::BoltNull)),
+            RecordedValue::LocalTime(s) => NaiveTime::parse_from_str(s, TIME_FORMAT).map(BoltType::from).unwrap_or(BoltType::Null(neo4rs::BoltNull)),
+            RecordedValue::LocalDateTime(s) => NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).map(BoltType::from).unwrap_or(BoltType::Null(neo4rs::BoltNull)),
+            RecordedValue::DateTime(s) => DateTime::parse_from_rfc3339(s).map(BoltType::from).unwrap_or(BoltType::Null(neo4rs::BoltNull)),
+            RecordedValue::Time { time, offset_seconds } => match (NaiveTime::parse_from_str(time, TIME_FORMAT), FixedOffset::east_opt(*offset_seconds)) {
+                (Ok(time), Some(offset)) => (time, offset).into(),
+                _ => BoltType::Null(neo4rs::BoltNull),
+            },
+            RecordedValue::DateTimeZoneId { datetime, zone } => NaiveDateTime::parse_from_str(datetime, DATETIME_FORMAT)
+                .map(|datetime| BoltType::from((datetime, zone.as_str())))
+                .unwrap_or(BoltType::Null(neo4rs::BoltNull)),
+            RecordedValue::Duration { months, days, seconds, nanoseconds } => BoltType::Duration(BoltDuration::new(
+                BoltInteger::new(*months),
+                BoltInteger::new(*days),
+                BoltInteger::new(*seconds),
+                BoltInteger::new(*nanoseconds),
+            )),
+        }
+    }
+}
+
+fn record_map(map: &BoltMap) -> BTreeMap<String, RecordedValue> {
+    map.value.iter().map(|(k, v)| (k.value.clone(), RecordedValue::from(v))).collect()
+}
+
+fn replay_map(map: &BTreeMap<String, RecordedValue>) -> BoltMap {
+    let mut bolt_map = BoltMap::new();
+    for (k, v) in map {
+        bolt_map.put(BoltString::new(k), v.into());
+    }
+    bolt_map
+}
+
+/// One execution captured by a RecordingExecutor: the query, its params and the rows it returned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub query: String,
+    pub params: BTreeMap<String, RecordedValue>,
+    pub rows: Vec<BTreeMap<String, RecordedValue>>,
+}
+
+/// Wraps another Executor and appends every execution to a file, one JSON Recording per line.
+/// The file can be replayed later with ReplayExecutor::from_file.
+/// # Example:
+/// ```rust
+/// let mut recorder = RecordingExecutor::new(graph, "tests/fixtures/users.jsonl");
+/// let result = builder.run_with(&mut recorder, EntityWrapper::from_db_entity).await?;
+/// ```
+pub struct RecordingExecutor<E: Executor> {
+    inner: E,
+    path: PathBuf,
+}
+
+impl<E: Executor> RecordingExecutor<E> {
+    pub fn new<P: AsRef<Path>>(inner: E, path: P) -> Self {
+        Self {
+            inner,
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+    /// Returns the wrapped executor, e.g. to commit a Txn after recording.
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: Executor> Executor for RecordingExecutor<E> {
+    async fn fetch_rows(&mut self, query: &str, params: &HashMap<String, BoltType>) -> Result<Vec<Row>> {
+        let rows = self.inner.fetch_rows(query, params).await?;
+        let mut recorded_rows = Vec::new();
+        for row in &rows {
+            let values: HashMap<String, BoltType> = row.to_strict().map_err(|e| Neo4gError::Db(neo4rs::Error::DeserializationError(e)))?;
+            recorded_rows.push(values.iter().map(|(k, v)| (k.clone(), RecordedValue::from(v))).collect());
+        }
+        let recording = Recording {
+            query: query.to_string(),
+            params: params.iter().map(|(k, v)| (k.clone(), RecordedValue::from(v))).collect(),
+            rows: recorded_rows,
+        };
+        let line = serde_json::to_string(&recording).map_err(|e| Neo4gError::Replay { reason: e.to_string() })?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)
+            .map_err(|e| Neo4gError::Replay { reason: format!("{}: {}", self.path.display(), e) })?;
+        writeln!(file, "{}", line).map_err(|e| Neo4gError::Replay { reason: format!("{}: {}", self.path.display(), e) })?;
+        Ok(rows)
+    }
+}
+
+/// Answers queries from canned rows instead of a database, keyed by query shape.
+/// The shape is the query text with whitespace collapsed and the random suffix of condition params removed,
+/// so the same builder code matches its recording on every run.
+/// Answers for the same shape are returned in order, and the last one is repeated once the others are used up.
+/// # Example:
+/// ```rust
+/// let mut replay = ReplayExecutor::from_file("tests/fixtures/users.jsonl")?;
+/// let result = builder.run_with(&mut replay, EntityWrapper::from_db_entity).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReplayExecutor {
+    answers: HashMap<String, VecDeque<Vec<BTreeMap<String, RecordedValue>>>>,
+    executed: Vec<(String, HashMap<String, BoltType>)>,
+}
+
+impl ReplayExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Loads the answers from a file written by RecordingExecutor.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| Neo4gError::Replay { reason: format!("{}: {}", path.display(), e) })?;
+        let mut replay = Self::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| Neo4gError::Replay { reason: format!("{}: {}", path.display(), e) })?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recording: Recording = serde_json::from_str(&line).map_err(|e| Neo4gError::Replay { reason: format!("{}: {}", path.display(), e) })?;
+            replay.answers.entry(query_shape(&recording.query)).or_default().push_back(recording.rows);
+        }
+        Ok(replay)
+    }
+    /// Adds an answer for a query. Each row maps a returned alias to its value.
+    /// # Example:
+    /// ```rust
+    /// replay.answer(&query, vec![HashMap::from([("user1".to_string(), BoltType::Node(user_node))])]);
+    /// ```
+    pub fn answer(&mut self, query: &str, rows: Vec<HashMap<String, BoltType>>) -> &mut Self {
+        let rows = rows.iter().map(|row| row.iter().map(|(k, v)| (k.clone(), RecordedValue::from(v))).collect()).collect();
+        self.answers.entry(query_shape(query)).or_default().push_back(rows);
+        self
+    }
+    /// Returns every query and its params that was run against this executor, in order.
+    pub fn executed(&self) -> &[(String, HashMap<String, BoltType>)] {
+        &self.executed
+    }
+}
+
+impl Executor for ReplayExecutor {
+    async fn fetch_rows(&mut self, query: &str, params: &HashMap<String, BoltType>) -> Result<Vec<Row>> {
+        self.executed.push((query.to_string(), params.clone()));
+        let shape = query_shape(query);
+        let answers = self.answers.get_mut(&shape).ok_or_else(|| Neo4gError::NoReplay { query: shape.clone() })?;
+        let rows = if answers.len() > 1 {
+            answers.pop_front().unwrap_or_default()
+        } else {
+            answers.front().cloned().unwrap_or_default()
+        };
+        Ok(rows.iter().map(|row| {
+            let mut fields = BoltList::new();
+            let mut data = BoltList::new();
+            for (k, v) in row {
+                fields.push(k.as_str().into());
+                data.push(v.into());
+            }
+            Row::new(fields, data)
+        }).collect())
+    }
+}
+
+/// Sorts RETURN aliases, collapses whitespace and strips the random four character suffix from condition params (co_name3f2a becomes co_name).
+pub(crate) fn query_shape(query: &str) -> String {
+    sort_return_aliases(query).split_whitespace().collect::<Vec<&str>>().join(" ")
+        .split('$')
+        .enumerate()
+        .map(|(i, part)| {
+            if i == 0 || !part.starts_with("co_") {
+                return part.to_string();
+            }
+            let end = part.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(part.len());
+            let (name, rest) = part.split_at(end);
+            match name.len().checked_sub(4) {
+                Some(cut) if cut > 3 && name[cut..].chars().all(|c| c.is_ascii_hexdigit()) => format!("{}{}", &name[..cut], rest),
+                _ => part.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("$")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_shape_strips_condition_suffixes() {
+        assert_eq!(
+            query_shape("MATCH (user1:User)\nWHERE user1.name = $co_name3f2a AND user1.age > $co_age09bc"),
+            "MATCH (user1:User) WHERE user1.name = $co_name AND user1.age > $co_age",
+        );
+    }
+
+    #[test]
+    fn query_shape_keeps_params_without_a_hex_suffix() {
+        assert_eq!(query_shape("MATCH (n) WHERE n.id = $co_idxyzw AND n.x = $user1_name"), "MATCH (n) WHERE n.id = $co_idxyzw AND n.x = $user1_name");
+    }
+
+    #[test]
+    fn query_shape_collapses_whitespace() {
+        assert_eq!(query_shape("MATCH   (n)\n\n  RETURN n"), "MATCH (n) RETURN n");
+    }
+
+    #[test]
+    fn query_shape_ignores_return_order() {
+        assert_eq!(query_shape("MATCH (a)-[r]->(b)\nRETURN b, a, r"), query_shape("MATCH (a)-[r]->(b)\nRETURN a, r, b"));
+    }
+
+    #[test]
+    fn query_shape_keeps_return_expressions_in_order() {
+        assert_eq!(query_shape("MATCH (n)\nRETURN n.name, count(n)"), "MATCH (n) RETURN n.name, count(n)");
+    }
+
+    fn round_trip(value: BoltType) -> BoltType {
+        let json = serde_json::to_string(&RecordedValue::from(&value)).unwrap();
+        BoltType::from(&serde_json::from_str::<RecordedValue>(&json).unwrap())
+    }
+
+    #[test]
+    fn durations_keep_negative_seconds() {
+        let duration = BoltType::Duration(BoltDuration::new(BoltInteger::new(0), BoltInteger::new(0), BoltInteger::new(-30), BoltInteger::new(500)));
+        assert_eq!(round_trip(duration.clone()), duration);
+    }
+
+    #[test]
+    fn durations_with_months_or_days_are_unsupported() {
+        let duration = BoltDuration::new(BoltInteger::new(14), BoltInteger::new(3), BoltInteger::new(30), BoltInteger::new(0));
+        assert_eq!(RecordedValue::from(&BoltType::Duration(duration)), RecordedValue::Unsupported("duration with months or days".to_string()));
+    }
+
+    #[test]
+    fn durations_written_by_hand_replay_their_months_and_days() {
+        let recorded: RecordedValue = serde_json::from_str(r#"{"Duration":{"months":14,"days":3,"seconds":30,"nanoseconds":0}}"#).unwrap();
+        let duration = BoltDuration::new(BoltInteger::new(14), BoltInteger::new(3), BoltInteger::new(30), BoltInteger::new(0));
+        assert_eq!(BoltType::from(&recorded), BoltType::Duration(duration));
+    }
+
+    #[test]
+    fn times_keep_their_offset() {
+        let time = NaiveTime::from_hms_nano_opt(13, 45, 7, 250_000_000).unwrap();
+        let value = BoltType::from((time, FixedOffset::east_opt(-5 * 3600).unwrap()));
+        assert_eq!(round_trip(value.clone()), value);
+    }
+
+    #[test]
+    fn datetimes_keep_their_zone_id() {
+        let datetime = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap().and_hms_opt(1, 30, 0).unwrap();
+        let value = BoltType::from((datetime, "Europe/Berlin"));
+        assert_eq!(round_trip(value.clone()), value);
+    }
+}
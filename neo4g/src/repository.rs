@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use neo4rs::{BoltMap, BoltString, BoltType, Graph, Node, Row};
+
+use crate::executor::{execute, execute_rows, Executor};
+use crate::query_builder::{DbEntityWrapper, DeletedFilter, EntityType, Neo4gError, Result, VERSION_PARAM_PREFIX};
+use crate::timestamps;
+use crate::traits::{Neo4gEntity, QueryParam};
+
+/// Which slice of the results find_all returns. The default returns every result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Paging {
+    pub skip: usize,
+    pub limit: Option<usize>,
+}
+
+impl Paging {
+    /// The page at the given zero-based index, with size results per page.
+    pub fn page(index: usize, size: usize) -> Self {
+        Self {
+            skip: index * size,
+            limit: Some(size),
+        }
+    }
+    /// Every result.
+    pub fn all() -> Self {
+        Self::default()
+    }
+}
+
+/// Simple CRUD for a node type, without a builder chain. Generated by Neo4gNode for structs marked #[neo4g(repository)].
+/// Nodes are matched on their key props, as returned by Neo4gEntity::key_props.
+/// Soft deleted nodes, as marked with #[neo4g(soft_delete)], are left out of find_by, find_all and count, and delete soft deletes them.
+/// # Example:
+/// ```rust
+/// #[derive(Neo4gNode, Serialize, Deserialize, Debug, Clone)]
+/// #[neo4g(repository)]
+/// pub struct UserTemplate {
+///     #[neo4g(key)]
+///     id: String,
+///     name: String,
+/// }
+///
+/// let user = User::find_by(&graph, UserProps::Id("8f8c54b6".into())).await?;
+/// let admins = User::find_all(&graph, &[UserProps::Name("admin".into())], Paging::page(0, 20)).await?;
+/// ```
+/// Each method has a _with variant that runs against any Executor, so it can be part of a transaction.
+pub trait Neo4gRepository: Neo4gEntity<Props: Clone> + Default + Send + Sync {
+    /// Builds the entity from a database node.
+    fn from_node(node: Node) -> Self;
+
+    /// Returns the first node matching the given prop, ordered by the key props.
+    /// NOTE: Only a key or unique prop identifies a single node. With any other prop, the node with the lowest key is returned.
+    fn find_by(graph: &Graph, prop: Self::Props) -> impl Future<Output = Result<Option<Self>>> + Send {
+        let (query, params) = find_by_query::<Self>(prop);
+        let mut graph = graph.clone();
+        async move {
+            Ok(fetch_nodes::<Self, Graph>(&mut graph, query, params).await?.into_iter().next())
+        }
+    }
+    /// Runs find_by against any Executor, such as a Txn or a RecordingExecutor.
+    fn find_by_with<E: Executor>(executor: &mut E, prop: Self::Props) -> impl Future<Output = Result<Option<Self>>> + Send {
+        let (query, params) = find_by_query::<Self>(prop);
+        async move {
+            Ok(fetch_nodes::<Self, E>(executor, query, params).await?.into_iter().next())
+        }
+    }
+    /// Returns the nodes matching every prop in filter, ordered by the key props. An empty filter matches every node.
+    fn find_all(graph: &Graph, filter: &[Self::Props], paging: Paging) -> impl Future<Output = Result<Vec<Self>>> + Send {
+        let (query, params) = find_all_query::<Self>(filter, paging);
+        let mut graph = graph.clone();
+        async move {
+            fetch_nodes::<Self, Graph>(&mut graph, query, params).await
+        }
+    }
+    /// Runs find_all against any Executor, such as a Txn or a RecordingExecutor.
+    fn find_all_with<E: Executor>(executor: &mut E, filter: &[Self::Props], paging: Paging) -> impl Future<Output = Result<Vec<Self>>> + Send {
+        let (query, params) = find_all_query::<Self>(filter, paging);
+        fetch_nodes::<Self, E>(executor, query, params)
+    }
+    /// Returns how many nodes match every prop in filter.
+    fn count(graph: &Graph, filter: &[Self::Props]) -> impl Future<Output = Result<usize>> + Send {
+        let (query, params) = count_query::<Self>(filter);
+        let mut graph = graph.clone();
+        async move {
+            fetch_count(&mut graph, query, params).await
+        }
+    }
+    /// Runs count against any Executor, such as a Txn or a RecordingExecutor.
+    fn count_with<E: Executor>(executor: &mut E, filter: &[Self::Props]) -> impl Future<Output = Result<usize>> + Send {
+        let (query, params) = count_query::<Self>(filter);
+        fetch_count(executor, query, params)
+    }
+    /// Merges the node on its key props, sets every non-excluded property, and returns the saved node.
+    /// An empty generated id is filled in first, so saving a new entity creates it.
+    /// A node with a field marked #[neo4g(version)] is only saved if its version still matches the entity's, and returns Neo4gError::ConcurrentModification otherwise.
+    /// Fields marked #[neo4g(created_at)] and #[neo4g(updated_at)] are set to the database time instead of their values.
+    fn save(&self, graph: &Graph) -> impl Future<Output = Result<Self>> + Send {
+        let (saved, query, params, versioned) = save_query(self);
+        let mut graph = graph.clone();
+        async move {
+            fetch_saved(&mut graph, saved, query, params, versioned).await
+        }
+    }
+    /// Runs save against any Executor, such as a Txn or a RecordingExecutor.
+    /// # Example:
+    /// ```rust
+    /// let user = user.save_with(&mut txn).await?;
+    /// ```
+    fn save_with<E: Executor>(&self, executor: &mut E) -> impl Future<Output = Result<Self>> + Send {
+        let (saved, query, params, versioned) = save_query(self);
+        fetch_saved(executor, saved, query, params, versioned)
+    }
+    /// Sets the given props on the node with the same key, and returns the updated node if it exists.
+    /// Current variants, such as UserProps::CurrentName, take their value from self.
+    /// A field marked #[neo4g(updated_at)] is also set to the database time, unless it is one of the props.
+    /// A node with a field marked #[neo4g(version)] is only updated if its version still matches the entity's, and the version is incremented.
+    /// If it no longer matches, or the node is gone, Neo4gError::ConcurrentModification is returned.
+    /// The BeforeSave hook runs when update is called, and the node is matched on the key props it leaves.
+    fn update(&self, graph: &Graph, props: &[Self::Props]) -> impl Future<Output = Result<Option<Self>>> + Send {
+        let (query, params, versioned) = update_query(self, props);
+        let mut graph = graph.clone();
+        async move {
+            fetch_updated::<Self, Graph>(&mut graph, query, params, versioned).await
+        }
+    }
+    /// Runs update against any Executor, such as a Txn or a RecordingExecutor.
+    /// # Example:
+    /// ```rust
+    /// let page = page.update_with(&mut txn, &[PageProps::CurrentPath]).await?;
+    /// ```
+    fn update_with<E: Executor>(&self, executor: &mut E, props: &[Self::Props]) -> impl Future<Output = Result<Option<Self>>> + Send {
+        let (query, params, versioned) = update_query(self, props);
+        fetch_updated::<Self, E>(executor, query, params, versioned)
+    }
+    /// Deletes the node with the same key. With detach, its relations are deleted too; without, nodes that have relations fail to delete.
+    /// Nodes with a field marked #[neo4g(soft_delete)] are soft deleted instead, and detach is ignored.
+    fn delete(&self, graph: &Graph, detach: bool) -> impl Future<Output = Result<()>> + Send {
+        let (query, params) = delete_query(self, detach);
+        let mut graph = graph.clone();
+        async move {
+            execute_rows(&mut graph, query, params, |_| Ok(())).await?;
+            Ok(())
+        }
+    }
+    /// Runs delete against any Executor, such as a Txn or a RecordingExecutor.
+    fn delete_with<E: Executor>(&self, executor: &mut E, detach: bool) -> impl Future<Output = Result<()>> + Send {
+        let (query, params) = delete_query(self, detach);
+        async move {
+            execute_rows(executor, query, params, |_| Ok(())).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Returns the (n:Label {props}) pattern and params for an entity.
+fn node_pattern<T: Neo4gEntity>(entity: &T, props: &[T::Props]) -> (String, HashMap<String, BoltType>) {
+    entity.entity_by("n", props)
+}
+
+/// Returns the WHERE clause that leaves out soft deleted nodes, unless the filter already includes the soft_delete field.
+/// Nodes without a value for the field count as not deleted.
+fn not_deleted<T: Neo4gEntity>(template: &T, filter: &[T::Props]) -> String {
+    template.soft_delete_prop(false)
+        .map(|prop| prop.to_query_param().0)
+        .filter(|key| !filter.iter().any(|existing| existing.to_query_param().0 == *key))
+        .and_then(|key| DeletedFilter::Exclude.condition("n", key))
+        .map(|condition| format!("\nWHERE {}", condition))
+        .unwrap_or_default()
+}
+
+/// Returns the ORDER BY items that sort nodes by their key props.
+fn key_order<T: Neo4gEntity>(template: &T) -> String {
+    template.key_props().iter().map(|prop| format!("n.{}", prop.to_query_param().0)).collect::<Vec<String>>().join(", ")
+}
+
+/// Returns the query and params that find a node by one prop. See Neo4gRepository::find_by.
+fn find_by_query<T: Neo4gRepository>(prop: T::Props) -> (String, HashMap<String, BoltType>) {
+    let template = T::default();
+    let (pattern, params) = node_pattern(&template, &[prop.clone()]);
+    let query = format!("MATCH {}{}\nRETURN n\nORDER BY {}\nLIMIT 1", pattern, not_deleted(&template, &[prop]), key_order(&template));
+    (query, params)
+}
+
+/// Returns the query and params that find a page of nodes. See Neo4gRepository::find_all.
+fn find_all_query<T: Neo4gRepository>(filter: &[T::Props], paging: Paging) -> (String, HashMap<String, BoltType>) {
+    let template = T::default();
+    let (pattern, mut params) = node_pattern(&template, filter);
+    let mut query = format!("MATCH {}{}\nRETURN n\nORDER BY {}\nSKIP $page_skip", pattern, not_deleted(&template, filter), key_order(&template));
+    params.insert("page_skip".to_string(), (paging.skip as i64).into());
+    if let Some(limit) = paging.limit {
+        query.push_str("\nLIMIT $page_limit");
+        params.insert("page_limit".to_string(), (limit as i64).into());
+    }
+    (query, params)
+}
+
+/// Returns the query and params that count nodes. See Neo4gRepository::count.
+fn count_query<T: Neo4gRepository>(filter: &[T::Props]) -> (String, HashMap<String, BoltType>) {
+    let template = T::default();
+    let (pattern, params) = node_pattern(&template, filter);
+    (format!("MATCH {}{}\nRETURN count(n) AS count", pattern, not_deleted(&template, filter)), params)
+}
+
+/// Returns the entity as it will be saved, the query and params that save it, and whether it is versioned. See Neo4gRepository::save.
+fn save_query<T: Neo4gRepository>(entity: &T) -> (T, String, HashMap<String, BoltType>, bool) {
+    let mut saved = entity.clone();
+    saved.ensure_id();
+    saved.hook_before_save();
+    let (pattern, mut params) = node_pattern(&saved, &saved.key_props());
+    let mut props = BoltMap::new();
+    let version = saved.version_prop().map(|prop| prop.to_query_param());
+    let version_key = version.as_ref().map(|(key, _)| *key);
+    for prop in saved.get_props() {
+        let (name, value) = prop.to_query_param();
+        if !timestamps::is_managed(&saved, name) && Some(name) != version_key {
+            props.put(BoltString::from(name), value);
+        }
+    }
+    params.insert("save_props".to_string(), BoltType::Map(props));
+    let updated_sets = timestamps::on_write_sets(&saved, "n");
+    let mut created_sets: Vec<String> = timestamps::on_create_sets(&saved, "n").into_iter().filter(|set| !updated_sets.contains(set)).collect();
+    let mut sets = vec!["n += $save_props".to_string()];
+    sets.extend(updated_sets);
+    let mut guard = String::new();
+    if let Some((key, value)) = version {
+        // A new node starts at the version being saved, so it passes the guard like an unchanged existing node.
+        let param = format!("{}n", VERSION_PARAM_PREFIX);
+        created_sets.push(format!("n.{} = ${}", key, param));
+        guard = format!("\nWITH n\nWHERE n.{} = ${}", key, param);
+        sets.push(format!("n.{} = n.{} + 1", key, key));
+        params.insert(param, value);
+    }
+    let mut query = format!("MERGE {}", pattern);
+    if !created_sets.is_empty() {
+        query.push_str(&format!("\nON CREATE SET {}", created_sets.join(", ")));
+    }
+    query.push_str(&format!("{}\nSET {}\nRETURN n", guard, sets.join(", ")));
+    let versioned = version_key.is_some();
+    (saved, query, params, versioned)
+}
+
+/// Returns the query and params that delete or soft delete an entity. See Neo4gRepository::delete.
+/// The BeforeDelete hook runs when it is called.
+fn delete_query<T: Neo4gRepository>(entity: &T, detach: bool) -> (String, HashMap<String, BoltType>) {
+    entity.hook_before_delete();
+    let (pattern, params) = node_pattern(entity, &entity.key_props());
+    let soft_delete_sets = entity.soft_delete_sets("n");
+    let query = if soft_delete_sets.is_empty() {
+        format!("MATCH {}\n{}DELETE n", pattern, if detach { "DETACH " } else { "" })
+    } else {
+        format!("MATCH {}\nSET {}", pattern, soft_delete_sets.join(", "))
+    };
+    (query, params)
+}
+
+/// Returns the query and params that update an entity, and whether it is versioned. See Neo4gRepository::update.
+fn update_query<T: Neo4gRepository>(entity: &T, props: &[T::Props]) -> (String, HashMap<String, BoltType>, bool) {
+    let mut saved = entity.clone();
+    saved.hook_before_save();
+    let (pattern, mut params) = node_pattern(&saved, &saved.key_props());
+    let mut sets: Vec<String> = props.iter().map(|prop| {
+        let (name, value) = saved.get_current(prop).to_query_param();
+        params.insert(format!("update_{}", name), value);
+        format!("n.{} = $update_{}", name, name)
+    }).collect();
+    let updated = saved.updated_at_prop().map(|(prop, _)| prop);
+    if !sets.is_empty() && !props.iter().any(|prop| Some(prop.to_query_param().0) == updated) {
+        sets.extend(timestamps::on_write_sets(&saved, "n"));
+    }
+    let mut guard = String::new();
+    let version = if sets.is_empty() { None } else { saved.version_prop() };
+    let versioned = version.is_some();
+    if let Some(version) = version {
+        let (key, value) = version.to_query_param();
+        let param = format!("{}n", VERSION_PARAM_PREFIX);
+        guard = format!("\nWHERE n.{} = ${}", key, param);
+        sets.push(format!("n.{} = n.{} + 1", key, key));
+        params.insert(param, value);
+    }
+    let query = if sets.is_empty() {
+        format!("MATCH {}\nRETURN n", pattern)
+    } else {
+        format!("MATCH {}{}\nSET {}\nRETURN n", pattern, guard, sets.join(", "))
+    };
+    (query, params, versioned)
+}
+
+async fn fetch_updated<T: Neo4gRepository, E: Executor>(executor: &mut E, query: String, params: HashMap<String, BoltType>, versioned: bool) -> Result<Option<T>> {
+    let node = fetch_nodes::<T, E>(executor, query.clone(), params).await?.into_iter().next();
+    if versioned && node.is_none() {
+        return Err(Neo4gError::ConcurrentModification { query });
+    }
+    Ok(node)
+}
+
+async fn fetch_saved<T: Neo4gRepository, E: Executor>(executor: &mut E, saved: T, query: String, params: HashMap<String, BoltType>, versioned: bool) -> Result<T> {
+    match fetch_nodes::<T, E>(executor, query.clone(), params).await?.into_iter().next() {
+        Some(node) => Ok(node),
+        None if versioned => Err(Neo4gError::ConcurrentModification { query }),
+        None => Ok(saved),
+    }
+}
+
+async fn fetch_count<E: Executor>(executor: &mut E, query: String, params: HashMap<String, BoltType>) -> Result<usize> {
+    let counts = execute_rows(executor, query, params, count_from_row).await?;
+    Ok(counts.into_iter().next().unwrap_or_default())
+}
+
+async fn fetch_nodes<T: Neo4gRepository, E: Executor>(executor: &mut E, query: String, params: HashMap<String, BoltType>) -> Result<Vec<T>> {
+    let return_refs = vec![("n".to_string(), EntityType::Node)];
+    let rows = execute(executor, query, params, &return_refs, &HashSet::new(), |db_entity| match db_entity {
+        DbEntityWrapper::Node(node) => Some(T::from_node(node)),
+        _ => None,
+    }).await?;
+    Ok(rows.into_iter().flatten().flatten().collect())
+}
+
+fn count_from_row(row: &Row) -> Result<usize> {
+    row.get::<i64>("count")
+        .map(|count| count as usize)
+        .map_err(|e| Neo4gError::from(neo4rs::Error::DeserializationError(e)))
+}
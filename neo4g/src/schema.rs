@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use heck::ToSnakeCase;
+use neo4rs::Graph;
+
+use crate::executor::{execute_rows, Executor};
+use crate::query_builder::{EntityType, Result};
+
+/// A single constraint or index declared on an entity with #[neo4g(...)].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaItem {
+    Unique(String),
+    CompositeUnique(Vec<String>),
+    NotNull(String),
+    Index(String),
+    NodeKey(Vec<String>),
+    Fulltext(Vec<String>),
+    Vector { property: String, dimensions: u32, similarity: String },
+}
+
+/// The constraints and indexes declared on one entity.
+/// Generated by the derive macros as Entity::schema().
+#[derive(Debug, Clone)]
+pub struct EntitySchema {
+    label: String,
+    entity_type: EntityType,
+    items: Vec<SchemaItem>,
+}
+
+impl EntitySchema {
+    pub fn new(label: String, entity_type: EntityType, items: Vec<SchemaItem>) -> Self {
+        Self {
+            label,
+            entity_type,
+            items,
+        }
+    }
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+    pub fn items(&self) -> &[SchemaItem] {
+        &self.items
+    }
+    /// Returns the idempotent CREATE CONSTRAINT/INDEX statements for this entity.
+    /// # Example:
+    /// ```rust
+    /// let statements = User::schema().statements();
+    /// ```
+    /// statements: ["CREATE CONSTRAINT user_id_unique IF NOT EXISTS FOR (n:User) REQUIRE n.id IS UNIQUE"]
+    pub fn statements(&self) -> Vec<String> {
+        let pattern = match self.entity_type {
+            EntityType::Relation => format!("()-[n:{}]-()", self.label),
+            _ => format!("(n:{})", self.label),
+        };
+        let name = self.label.to_snake_case();
+        self.items.iter().map(|item| {
+            match item {
+                SchemaItem::Unique(prop) => format!("CREATE CONSTRAINT {}_{}_unique IF NOT EXISTS FOR {} REQUIRE n.{} IS UNIQUE", name, prop, pattern, prop),
+                SchemaItem::CompositeUnique(props) => {
+                    let fields = props.iter().map(|p| format!("n.{}", p)).collect::<Vec<String>>().join(", ");
+                    format!("CREATE CONSTRAINT {}_{}_unique IF NOT EXISTS FOR {} REQUIRE ({}) IS UNIQUE", name, props.join("_"), pattern, fields)
+                },
+                SchemaItem::NotNull(prop) => format!("CREATE CONSTRAINT {}_{}_not_null IF NOT EXISTS FOR {} REQUIRE n.{} IS NOT NULL", name, prop, pattern, prop),
+                SchemaItem::Index(prop) => format!("CREATE INDEX {}_{}_index IF NOT EXISTS FOR {} ON (n.{})", name, prop, pattern, prop),
+                SchemaItem::NodeKey(props) => {
+                    let key = match self.entity_type {
+                        EntityType::Relation => "RELATIONSHIP KEY",
+                        _ => "NODE KEY",
+                    };
+                    let fields = props.iter().map(|p| format!("n.{}", p)).collect::<Vec<String>>().join(", ");
+                    format!("CREATE CONSTRAINT {}_{}_key IF NOT EXISTS FOR {} REQUIRE ({}) IS {}", name, props.join("_"), pattern, fields, key)
+                },
+                SchemaItem::Fulltext(props) => {
+                    let fields = props.iter().map(|p| format!("n.{}", p)).collect::<Vec<String>>().join(", ");
+                    format!("CREATE FULLTEXT INDEX {}_fulltext IF NOT EXISTS FOR {} ON EACH [{}]", name, pattern, fields)
+                },
+                SchemaItem::Vector { property, dimensions, similarity } => format!(
+                    "CREATE VECTOR INDEX {}_{}_vector IF NOT EXISTS FOR {} ON (n.{}) OPTIONS {{indexConfig: {{`vector.dimensions`: {}, `vector.similarity_function`: '{}'}}}}",
+                    name, property, pattern, property, dimensions, similarity
+                ),
+            }
+        }).collect()
+    }
+}
+
+/// Applies the constraints and indexes declared on entities to the database.
+pub struct Neo4gSchema;
+
+impl Neo4gSchema {
+    /// Issues CREATE CONSTRAINT/INDEX IF NOT EXISTS for every declared item, so it is safe to run on every startup.
+    /// # Example:
+    /// ```rust
+    /// Neo4gSchema::ensure(&graph, &[User::schema(), Group::schema()]).await?;
+    /// ```
+    /// The statements run through the registered interceptors, like any other execution.
+    pub async fn ensure(graph: &Graph, schemas: &[EntitySchema]) -> Result<()> {
+        Self::ensure_with(&mut graph.clone(), schemas).await
+    }
+    /// Runs ensure against any Executor, such as a Txn or a RecordingExecutor.
+    /// # Example:
+    /// ```rust
+    /// Neo4gSchema::ensure_with(&mut recorder, &[User::schema()]).await?;
+    /// ```
+    pub async fn ensure_with<E: Executor>(executor: &mut E, schemas: &[EntitySchema]) -> Result<()> {
+        for schema in schemas {
+            for statement in schema.statements() {
+                execute_rows(executor, statement, HashMap::new(), |_| Ok(())).await?;
+            }
+        }
+        Ok(())
+    }
+}
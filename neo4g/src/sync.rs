@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use neo4rs::{BoltList, BoltMap, BoltString, BoltType, Graph, Row, Txn};
+
+use crate::executor::{execute_rows, Executor};
+use crate::query_builder::{Neo4gBuilder, Neo4gError, Result};
+use crate::render::pretty_cypher;
+use crate::timestamps;
+use crate::traits::{Empty, Neo4gEntity, QueryParam};
+
+/// How sync_relations removes relations to nodes that are no longer in the desired set.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    soft_delete: Option<String>,
+}
+
+impl SyncOptions {
+    /// Sets the given boolean property to true on extra relations instead of deleting them.
+    /// Soft deleted relations to nodes in the desired set are restored by setting it back to false, and count as added.
+    pub fn soft_delete(mut self, property: &str) -> Self {
+        self.soft_delete = Some(property.to_string());
+        self
+    }
+}
+
+/// How many relations a sync added and removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// A query that reconciles the outgoing relations of one node to a desired set of target nodes.
+#[derive(Debug, Clone)]
+pub struct RelationSync {
+    query: String,
+    params: HashMap<String, BoltType>,
+}
+
+impl Neo4gBuilder<Empty> {
+    /// Reconciles the relations of the given type from one node to the target nodes, matched by their key property.
+    /// Missing relations are created with every non-excluded property of the relation, and relations to any other node with the target label are removed.
+    /// # Example
+    /// ```rust
+    /// let report = Neo4gBuilder::new()
+    ///     .sync_relations(&user, prop!(user.id), &member_of, &groups, prop!(group.id), SyncOptions::default().soft_delete("deleted"))
+    ///     .run_query(graph).await?;
+    /// println!("added {}, removed {}", report.added, report.removed);
+    /// ```
+    /// The example above generates the following query:
+    /// ```rust
+    /// MATCH (from:User {id: $sync_from_key})
+    /// CALL {
+    ///     WITH from
+    ///     UNWIND $sync_to_keys AS key
+    ///     MATCH (to:Group {id: key})
+    ///     OPTIONAL MATCH (from)-[existing:MEMBER_OF]->(to)
+    ///     WITH from, to, existing
+    ///     WHERE existing IS NULL OR existing.deleted = true
+    ///     MERGE (from)-[rel:MEMBER_OF]->(to)
+    ///     ON CREATE SET rel = $sync_props
+    ///     SET rel.deleted = false
+    ///     RETURN count(rel) AS added
+    /// }
+    /// CALL {
+    ///     WITH from
+    ///     MATCH (from)-[rel:MEMBER_OF]->(to:Group)
+    ///     WHERE NOT to.id IN $sync_to_keys AND coalesce(rel.deleted, false) = false
+    ///     SET rel.deleted = true
+    ///     RETURN count(rel) AS removed
+    /// }
+    /// RETURN added, removed
+    /// ```
+    /// Relations with a field marked #[neo4g(soft_delete)] are soft deleted on that field, unless the options name another.
+    /// Fields of the relation marked #[neo4g(created_at)] or #[neo4g(updated_at)] are set to the database time when it is created, and updated_at again when it is soft deleted or restored.
+    /// NOTE: An empty set of targets removes every relation of the given type to nodes with the target label.
+    pub fn sync_relations<A, FA, R, B, FB>(self, from: &A, from_key: FA, relation: &R, to: &[B], to_key: FB, options: SyncOptions) -> RelationSync
+    where A: Neo4gEntity, FA: FnOnce(&A) -> A::Props, R: Neo4gEntity, B: Neo4gEntity + Default, FB: Fn(&B) -> B::Props {
+        let (from_key, from_value) = from_key(from).to_query_param();
+        let to_label = B::default().get_label();
+        let mut to_key_name = "";
+        let mut to_keys: Vec<BoltType> = Vec::new();
+        for target in to {
+            let (key, value) = to_key(target).to_query_param();
+            to_key_name = key;
+            if !to_keys.contains(&value) {
+                to_keys.push(value);
+            }
+        }
+        if to_key_name.is_empty() {
+            to_key_name = "id";
+        }
+        let mut relation = relation.clone();
+        relation.hook_before_save();
+        let relation = &relation;
+        let mut props = BoltMap::new();
+        for prop in relation.get_props() {
+            let (name, value) = prop.to_query_param();
+            if !timestamps::is_managed(relation, name) {
+                props.put(BoltString::from(name), value);
+            }
+        }
+        let soft_delete = options.soft_delete.clone().or_else(|| relation.soft_delete_prop(true).map(|prop| prop.to_query_param().0.to_string()));
+        let query = sync_query(
+            &from.get_label(),
+            from_key,
+            &relation.get_label(),
+            &to_label,
+            to_key_name,
+            soft_delete.as_deref(),
+            &timestamps::on_create_sets(relation, "rel"),
+            &timestamps::on_write_sets(relation, "rel"),
+        );
+        let mut params = HashMap::new();
+        params.insert("sync_from_key".to_string(), from_value);
+        params.insert("sync_to_keys".to_string(), BoltType::List(BoltList::from(to_keys)));
+        params.insert("sync_props".to_string(), BoltType::Map(props));
+        RelationSync { query, params }
+    }
+}
+
+/// Builds the sync query. Relations are soft deleted on the given property, or deleted outright without one.
+/// The subqueries import from with WITH rather than a variable scope clause, so they run on every supported Neo4j version.
+fn sync_query(from_label: &str, from_key: &str, rel_type: &str, to_label: &str, to_key: &str, soft_delete: Option<&str>, created_sets: &[String], updated_sets: &[String]) -> String {
+    let created: String = created_sets.iter().map(|set| format!(", {}", set)).collect();
+    let updated: String = updated_sets.iter().map(|set| format!(", {}", set)).collect();
+    let (restorable, restore, removable, remove) = match soft_delete {
+        Some(property) => (
+            format!("existing IS NULL OR existing.{} = true", property),
+            format!("\nSET rel.{} = false{}", property, updated),
+            format!(" AND coalesce(rel.{}, false) = false", property),
+            format!("SET rel.{} = true{}", property, updated),
+        ),
+        None => ("existing IS NULL".to_string(), String::new(), String::new(), "DELETE rel".to_string()),
+    };
+    format!(
+        "MATCH (from:{from_label} {{{from_key}: $sync_from_key}})\n\
+        CALL {{\n\
+        WITH from\n\
+        UNWIND $sync_to_keys AS key\n\
+        MATCH (to:{to_label} {{{to_key}: key}})\n\
+        OPTIONAL MATCH (from)-[existing:{rel_type}]->(to)\n\
+        WITH from, to, existing\n\
+        WHERE {restorable}\n\
+        MERGE (from)-[rel:{rel_type}]->(to)\n\
+        ON CREATE SET rel = $sync_props{created}{restore}\n\
+        RETURN count(rel) AS added\n\
+        }}\n\
+        CALL {{\n\
+        WITH from\n\
+        MATCH (from)-[rel:{rel_type}]->(to:{to_label})\n\
+        WHERE NOT to.{to_key} IN $sync_to_keys{removable}\n\
+        {remove}\n\
+        RETURN count(rel) AS removed\n\
+        }}\n\
+        RETURN added, removed"
+    )
+}
+
+impl RelationSync {
+    /// Returns the query and params.
+    pub fn build(self) -> (String, HashMap<String, BoltType>) {
+        (self.query, self.params)
+    }
+    /// Returns the query, indented with one clause per line.
+    pub fn to_cypher_pretty(&self) -> String {
+        pretty_cypher(&self.query)
+    }
+    /// Runs the sync against a provided Graph and reports how many relations were added and removed.
+    /// # Example:
+    /// ```rust
+    /// .run_query(graph).await;
+    /// ```
+    pub async fn run_query(self, mut graph: Graph) -> Result<SyncReport> {
+        self.run_with(&mut graph).await
+    }
+    /// Runs the sync within a provided Txn.
+    /// # Example:
+    /// ```rust
+    /// .execute_in_txn(txn).await;
+    /// ```
+    pub async fn execute_in_txn(self, txn: &mut Txn) -> Result<SyncReport> {
+        self.run_with(txn).await
+    }
+    /// Runs the sync against any Executor.
+    /// If the start node does not exist, nothing is changed and the report is empty.
+    /// # Example:
+    /// ```rust
+    /// .run_with(&mut recording_executor).await;
+    /// ```
+    pub async fn run_with<E: Executor>(self, executor: &mut E) -> Result<SyncReport> {
+        let reports = execute_rows(executor, self.query, self.params, report_from_row).await?;
+        Ok(reports.into_iter().next().unwrap_or_default())
+    }
+}
+
+fn report_from_row(row: &Row) -> Result<SyncReport> {
+    let count = |key: &str| {
+        row.get::<i64>(key)
+            .map(|count| count as usize)
+            .map_err(|e| Neo4gError::from(neo4rs::Error::DeserializationError(e)))
+    };
+    Ok(SyncReport {
+        added: count("added")?,
+        removed: count("removed")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relations_without_a_soft_delete_property_are_deleted() {
+        let query = sync_query("User", "id", "MEMBER_OF", "Group", "id", None, &[], &[]);
+        assert_eq!(query, "MATCH (from:User {id: $sync_from_key})
+CALL {
+WITH from
+UNWIND $sync_to_keys AS key
+MATCH (to:Group {id: key})
+OPTIONAL MATCH (from)-[existing:MEMBER_OF]->(to)
+WITH from, to, existing
+WHERE existing IS NULL
+MERGE (from)-[rel:MEMBER_OF]->(to)
+ON CREATE SET rel = $sync_props
+RETURN count(rel) AS added
+}
+CALL {
+WITH from
+MATCH (from)-[rel:MEMBER_OF]->(to:Group)
+WHERE NOT to.id IN $sync_to_keys
+DELETE rel
+RETURN count(rel) AS removed
+}
+RETURN added, removed");
+    }
+
+    #[test]
+    fn relations_with_a_soft_delete_property_are_soft_deleted_and_restored() {
+        let created = vec!["rel.created = localdatetime()".to_string(), "rel.updated = localdatetime()".to_string()];
+        let updated = vec!["rel.updated = localdatetime()".to_string()];
+        let query = sync_query("User", "id", "MEMBER_OF", "Group", "id", Some("deleted"), &created, &updated);
+        assert_eq!(query, "MATCH (from:User {id: $sync_from_key})
+CALL {
+WITH from
+UNWIND $sync_to_keys AS key
+MATCH (to:Group {id: key})
+OPTIONAL MATCH (from)-[existing:MEMBER_OF]->(to)
+WITH from, to, existing
+WHERE existing IS NULL OR existing.deleted = true
+MERGE (from)-[rel:MEMBER_OF]->(to)
+ON CREATE SET rel = $sync_props, rel.created = localdatetime(), rel.updated = localdatetime()
+SET rel.deleted = false, rel.updated = localdatetime()
+RETURN count(rel) AS added
+}
+CALL {
+WITH from
+MATCH (from)-[rel:MEMBER_OF]->(to:Group)
+WHERE NOT to.id IN $sync_to_keys AND coalesce(rel.deleted, false) = false
+SET rel.deleted = true, rel.updated = localdatetime()
+RETURN count(rel) AS removed
+}
+RETURN added, removed");
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Instant;
+
+use neo4rs::{BoltType, Neo4jErrorKind};
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::interceptor::QueryOutcome;
+use crate::query_builder::Neo4gError;
+use crate::render::to_cypher_literal;
+
+static PARAM_POLICY: RwLock<ParamPolicy> = RwLock::new(ParamPolicy::Redact);
+
+/// Decides which param values are recorded on query spans. Param keys are always recorded.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ParamPolicy {
+    /// Record every value as <redacted>. This is the default.
+    #[default]
+    Redact,
+    /// Record every value as a Cypher literal.
+    Show,
+    /// Record values whose param key passes the check, and redact the rest.
+    ShowIf(fn(&str) -> bool),
+}
+
+/// Sets the param policy for every query span recorded from now on.
+/// # Example:
+/// ```rust
+/// set_param_policy(ParamPolicy::ShowIf(|key| !key.contains("password")));
+/// ```
+pub fn set_param_policy(policy: ParamPolicy) {
+    *PARAM_POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+/// Returns a short classification of an error, as recorded in the error.kind field of query spans.
+pub fn error_kind(error: &Neo4gError) -> &'static str {
+    match error {
+        Neo4gError::Db(neo4rs::Error::Neo4j(e)) => match e.kind() {
+            Neo4jErrorKind::Client(_) => "client",
+            Neo4jErrorKind::Transient => "transient",
+            Neo4jErrorKind::Database => "database",
+            Neo4jErrorKind::Unknown => "unknown",
+        },
+        Neo4gError::Db(neo4rs::Error::IOError { .. }) | Neo4gError::Db(neo4rs::Error::ConnectionError) => "connection",
+        Neo4gError::Db(neo4rs::Error::AuthenticationError(_)) => "auth",
+        Neo4gError::Db(neo4rs::Error::DeserializationError(_)) => "decode",
+        Neo4gError::Db(_) => "driver",
+        Neo4gError::ConstraintViolation { .. } => "constraint",
+        Neo4gError::Syntax { .. } => "syntax",
+        Neo4gError::Transient { .. } => "transient",
+        Neo4gError::Auth { .. } => "auth",
+        Neo4gError::Decode { .. } => "decode",
+        Neo4gError::NodeNotFound { .. } | Neo4gError::RelationNotFound { .. } | Neo4gError::UnexpectedError { .. } => "decode",
+        _ => "neo4g",
+    }
+}
+
+/// Opens a span for one query execution, recording the query text and params according to the param policy.
+pub(crate) fn query_span(query: &str, params: &HashMap<String, BoltType>) -> Span {
+    tracing::info_span!(
+        "neo4g.query",
+        db.system = "neo4j",
+        db.statement = %query,
+        db.params = %render_params(params),
+        db.rows = Empty,
+        elapsed_ms = Empty,
+        error.kind = Empty,
+    )
+}
+
+/// Records the row count or error classification, and the elapsed time, on a query span.
+pub(crate) fn record_outcome(span: &Span, start: Instant, outcome: &QueryOutcome) {
+    span.record("elapsed_ms", start.elapsed().as_millis() as u64);
+    match outcome {
+        QueryOutcome::Rows(rows) => {
+            span.record("db.rows", rows);
+            tracing::debug!(parent: span, "query completed");
+        },
+        QueryOutcome::Error(e) => {
+            span.record("error.kind", error_kind(e));
+            tracing::warn!(parent: span, error = %e, "query failed");
+        },
+    }
+}
+
+fn render_params(params: &HashMap<String, BoltType>) -> String {
+    let policy = *PARAM_POLICY.read().unwrap_or_else(|e| e.into_inner());
+    let mut keys: Vec<&String> = params.keys().collect();
+    keys.sort();
+    let rendered: Vec<String> = keys.iter().map(|key| {
+        let show = match policy {
+            ParamPolicy::Redact => false,
+            ParamPolicy::Show => true,
+            ParamPolicy::ShowIf(check) => check(key),
+        };
+        if show {
+            format!("{}: {}", key, to_cypher_literal(&params[*key]))
+        } else {
+            format!("{}: <redacted>", key)
+        }
+    }).collect();
+    format!("{{{}}}", rendered.join(", "))
+}
@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use neo4rs::BoltType;
+
+use crate::traits::Neo4gEntity;
+
+/// Returns the SET items for a newly created entity: both the created_at and updated_at props.
+pub(crate) fn on_create_sets<T: Neo4gEntity>(entity: &T, alias: &str) -> Vec<String> {
+    [entity.created_at_prop(), entity.updated_at_prop()].into_iter().flatten()
+        .map(|(prop, function)| format!("{}.{} = {}", alias, prop, function))
+        .collect()
+}
+
+/// Returns the SET items for any write to an entity: only the updated_at prop.
+pub(crate) fn on_write_sets<T: Neo4gEntity>(entity: &T, alias: &str) -> Vec<String> {
+    entity.updated_at_prop().into_iter()
+        .map(|(prop, function)| format!("{}.{} = {}", alias, prop, function))
+        .collect()
+}
+
+/// Whether the property is set by the database rather than from the entity.
+pub(crate) fn is_managed<T: Neo4gEntity>(entity: &T, prop: &str) -> bool {
+    [entity.created_at_prop(), entity.updated_at_prop()].into_iter().flatten().any(|(managed, _)| managed == prop)
+}
+
+/// Swaps the params of managed props in a pattern from create_from_self for the Cypher function that sets them, so the database clock is used.
+pub(crate) fn server_side_pattern<T: Neo4gEntity>(entity: &T, pattern: &str, params: &mut HashMap<String, BoltType>) -> String {
+    let alias = entity.get_alias().to_lowercase();
+    let mut pattern = pattern.to_string();
+    for (prop, function) in [entity.created_at_prop(), entity.updated_at_prop()].into_iter().flatten() {
+        let param = format!("{}_{}", alias, prop);
+        pattern = pattern.replace(&format!("{}: ${}", prop, param), &format!("{}: {}", prop, function));
+        params.remove(&param);
+    }
+    pattern
+}
@@ -9,6 +9,42 @@ use crate::query_builder::{DbEntityWrapper, EntityType, Expr};
 pub trait WrappedNeo4gEntity: Sized + Aliasable {
     fn from_db_entity(db_entity: DbEntityWrapper) -> Self;
     fn get_entity_type(&self) -> EntityType;
+    /// Returns the SET items that soft delete the wrapped entity. See Neo4gEntity::soft_delete_sets.
+    fn soft_delete_sets(&self, _alias: &str) -> Vec<String> { Vec::new() }
+    /// Runs the BeforeDelete hook of the wrapped entity. See Neo4gEntity::hook_before_delete.
+    fn hook_before_delete(&self) {}
+}
+
+/// Runs before an entity is written by a create, merge or set, e.g. to normalise fields or hash a password.
+/// Wired up by deriving with #[neo4g(hooks)] or #[neo4g(hooks(before_save))].
+/// It runs once per entity per query, when the query is built rather than when it is sent, so a prepared query that is run
+/// many times reuses the values it left. create().node/relation and merge().node/relation run it on the entity passed in,
+/// so the entity holds the saved values afterwards, and a later .set() of that entity in the same query doesn't run it again.
+/// A .set() of any other entity, and the bulk writes and repository methods, run it on a copy and leave the entity as it was.
+/// # Example:
+/// ```rust
+/// impl BeforeSave for Page {
+///     fn before_save(&mut self) {
+///         let path = self.path().trim_end_matches('/').to_lowercase();
+///         self.path = PageProps::Path(path);
+///     }
+/// }
+/// ```
+pub trait BeforeSave {
+    fn before_save(&mut self);
+}
+
+/// Runs after an entity is decoded from the database, e.g. to compute derived fields.
+/// Wired up by deriving with #[neo4g(hooks)] or #[neo4g(hooks(after_load))].
+pub trait AfterLoad {
+    fn after_load(&mut self);
+}
+
+/// Runs before an entity is deleted or soft deleted by a match statement or a repository.
+/// Wired up by deriving with #[neo4g(hooks)] or #[neo4g(hooks(before_delete))].
+/// Like BeforeSave, it runs when the query is built, so it also runs for a query that is never sent or fails.
+pub trait BeforeDelete {
+    fn before_delete(&self);
 }
 
 pub trait Neo4gLabel: std::fmt::Display {}
@@ -19,6 +55,38 @@ pub trait Neo4gEntity: Aliasable {
     fn get_label(&self) -> String;
     fn entity_by(&self, alias: &str, props: &[Self::Props]) -> (String, std::collections::HashMap<String, BoltType>);
     fn create_from_self(&self) -> (String, std::collections::HashMap<String, BoltType>);
+    fn get_props(&self) -> Vec<Self::Props>;
+    /// Returns the props that identify the entity, as marked with #[neo4g(key)].
+    fn key_props(&self) -> Vec<Self::Props>;
+    /// Fills in the field marked #[neo4g(id = ...)] if it is empty. Does nothing for entities without one.
+    fn ensure_id(&mut self) {}
+    /// Returns the property marked #[neo4g(created_at)] and the Cypher function that sets it, such as ("created", "localdatetime()").
+    fn created_at_prop(&self) -> Option<(&'static str, &'static str)> { None }
+    /// Returns the property marked #[neo4g(updated_at)] and the Cypher function that sets it.
+    fn updated_at_prop(&self) -> Option<(&'static str, &'static str)> { None }
+    /// Returns the field marked #[neo4g(soft_delete)] with the given value, for matching on whether the entity is deleted.
+    fn soft_delete_prop(&self, _deleted: bool) -> Option<Self::Props> { None }
+    /// Returns the property marked #[neo4g(deleted_at)] and the Cypher function that sets it when the entity is soft deleted.
+    fn deleted_at_prop(&self) -> Option<(&'static str, &'static str)> { None }
+    /// Returns the field marked #[neo4g(version)], holding the version the entity was read at.
+    fn version_prop(&self) -> Option<Self::Props> { None }
+    /// Runs the BeforeSave hook, if the entity was derived with one.
+    fn hook_before_save(&mut self) {}
+    /// Runs the AfterLoad hook, if the entity was derived with one.
+    fn hook_after_load(&mut self) {}
+    /// Runs the BeforeDelete hook, if the entity was derived with one.
+    fn hook_before_delete(&self) {}
+    /// Returns the SET items that soft delete the entity under the given alias, which also set its deleted_at and updated_at props.
+    /// Entities without a field marked #[neo4g(soft_delete)] return none, and are deleted outright.
+    fn soft_delete_sets(&self, alias: &str) -> Vec<String> {
+        let prop = match self.soft_delete_prop(true) {
+            Some(prop) => prop,
+            None => return Vec::new(),
+        };
+        let mut sets = vec![format!("{}.{} = true", alias, prop.to_query_param().0)];
+        sets.extend(self.deleted_at_prop().into_iter().chain(self.updated_at_prop()).map(|(prop, function)| format!("{}.{} = {}", alias, prop, function)));
+        sets
+    }
     fn get_current(&self, prop: &Self::Props) -> Self::Props;
 }
 
@@ -34,8 +102,20 @@ pub trait Aliasable: std::fmt::Debug + Clone + Paramable {
     fn get_uuid(&self) -> Uuid;
 }
 
+/// Implemented by Neo4gNode for every node, so that APIs which only make sense for nodes reject relations at compile time.
+pub trait NodeEntity: Neo4gEntity {}
+
+/// Implemented by Neo4gNode for nodes with a field marked #[neo4g(key)], #[neo4g(node_key)] or #[neo4g(unique)],
+/// so that node_by_key only accepts nodes whose key_props are never empty.
+pub trait KeyedNode: NodeEntity {}
+
 pub trait QueryParam {
     fn to_query_param(&self) -> (&'static str, BoltType);
+    /// The label of the node or relation the prop belongs to, which scopes PreparedQuery::bind to that entity.
+    /// Props that do not belong to a single entity return None.
+    fn entity_label(&self) -> Option<String> {
+        None
+    }
 }
 
 pub trait ToExpr {
@@ -83,6 +163,9 @@ pub struct DeletedEntity;
 #[derive(Debug, Clone)]
 pub struct Withed;
 
+#[derive(Debug, Clone)]
+pub struct Rawed;
+
 #[derive(Debug, Clone)]
 pub struct WithCondition;
 
@@ -108,6 +191,10 @@ impl CanWith for Called {}
 impl CanMatch for Called {}
 impl CanCreate for Called {}
 impl PossibleQueryEnd for Called {}
+impl CanWith for Rawed {}
+impl CanMatch for Rawed {}
+impl CanCreate for Rawed {}
+impl PossibleQueryEnd for Rawed {}
 impl CanWith for Empty {}
 impl PossibleStatementEnd for MatchedNode {}
 impl PossibleStatementEnd for CreatedNode {}
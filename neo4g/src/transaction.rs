@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use neo4rs::{Graph, Txn};
+
+use crate::query_builder::{Neo4gError, Result};
+
+/// How transaction() retries a closure that failed with a transient error, such as a deadlock.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt. 0 disables retrying.
+    pub max_retries: u32,
+    /// The wait before the first retry. It doubles on each retry up to max_backoff.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Runs a closure in a transaction, commits if it returns Ok and rolls back if it returns Err.
+/// Transient failures, including deadlocks, retry the whole closure with backoff using the default RetryPolicy.
+/// A failed commit is not retried, as it may have been applied anyway, and returns Neo4gError::CommitOutcomeUnknown.
+/// # Example:
+/// ```rust
+/// let users = neo4g::transaction(&graph, async |txn| {
+///     let users = Neo4gBuilder::new()
+///         .get()
+///             .node(&mut user, props!(user => user.id)).add_to_return()
+///         .end_statement()
+///         .execute_in_txn(txn, EntityWrapper::from_db_entity).await?;
+///     Neo4gBuilder::new()
+///         .create()
+///             .node(&mut group, &[])
+///         .end_statement()
+///         .execute_in_txn(txn, EntityWrapper::from_db_entity).await?;
+///     Ok(users)
+/// }).await?;
+/// ```
+pub async fn transaction<T, F>(graph: &Graph, f: F) -> Result<T>
+where F: AsyncFnMut(&mut Txn) -> Result<T> {
+    transaction_with(graph, &RetryPolicy::default(), f).await
+}
+
+/// Same as transaction(), with a custom RetryPolicy.
+pub async fn transaction_with<T, F>(graph: &Graph, policy: &RetryPolicy, f: F) -> Result<T>
+where F: AsyncFnMut(&mut Txn) -> Result<T> {
+    retry(graph, policy, f).await
+}
+
+/// Starts, commits and rolls back the transactions that a closure is retried in.
+/// Implemented for Graph, and for fakes in tests.
+trait TxnSource {
+    type Txn;
+    async fn begin(&self) -> Result<Self::Txn>;
+    async fn commit(txn: Self::Txn) -> Result<()>;
+    async fn rollback(txn: Self::Txn) -> Result<()>;
+}
+
+impl TxnSource for Graph {
+    type Txn = Txn;
+    async fn begin(&self) -> Result<Txn> {
+        Ok(self.start_txn().await?)
+    }
+    async fn commit(txn: Txn) -> Result<()> {
+        Ok(txn.commit().await?)
+    }
+    async fn rollback(txn: Txn) -> Result<()> {
+        Ok(txn.rollback().await?)
+    }
+}
+
+async fn retry<S, T, F>(source: &S, policy: &RetryPolicy, mut f: F) -> Result<T>
+where S: TxnSource, F: AsyncFnMut(&mut S::Txn) -> Result<T> {
+    let mut attempt = 0;
+    let mut backoff = policy.initial_backoff;
+    loop {
+        match attempt_once(source, &mut f).await {
+            Err(e) if e.is_transient() && attempt < policy.max_retries => {
+                tracing::debug!(attempt, error = %e, "retrying transaction after transient error");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(policy.max_backoff);
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+async fn attempt_once<S, T, F>(source: &S, f: &mut F) -> Result<T>
+where S: TxnSource, F: AsyncFnMut(&mut S::Txn) -> Result<T> {
+    let mut txn = source.begin().await?;
+    match f(&mut txn).await {
+        Ok(value) => match S::commit(txn).await {
+            Ok(()) => Ok(value),
+            Err(e) => Err(commit_error(e)),
+        },
+        Err(e) => {
+            // The closure's error is the one worth reporting, so a failed rollback is only logged.
+            if let Err(rollback_error) = S::rollback(txn).await {
+                tracing::warn!(error = %rollback_error, "failed to roll back transaction");
+            }
+            Err(e)
+        },
+    }
+}
+
+/// Wraps an error raised while committing, which is classified with the COMMIT as its query.
+fn commit_error(e: Neo4gError) -> Neo4gError {
+    let query = "COMMIT".to_string();
+    let source = Box::new(e.with_query(&query, &HashMap::new()));
+    Neo4gError::CommitOutcomeUnknown { query, source }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use neo4rs::{BoltType, Row};
+
+    use super::*;
+    use crate::executor::Executor;
+
+    /// Hands out transactions that fail their first `failures` queries with a transient error, and logs what happens to them.
+    #[derive(Default)]
+    struct FakeDb {
+        failures: Mutex<u32>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct FakeTxn {
+        transient: bool,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TxnSource for FakeDb {
+        type Txn = FakeTxn;
+        async fn begin(&self) -> Result<FakeTxn> {
+            let mut failures = self.failures.lock().unwrap();
+            let transient = *failures > 0;
+            *failures = failures.saturating_sub(1);
+            self.log.lock().unwrap().push("begin".to_string());
+            Ok(FakeTxn { transient, log: self.log.clone() })
+        }
+        async fn commit(txn: FakeTxn) -> Result<()> {
+            txn.log.lock().unwrap().push("commit".to_string());
+            Ok(())
+        }
+        async fn rollback(txn: FakeTxn) -> Result<()> {
+            txn.log.lock().unwrap().push("rollback".to_string());
+            Ok(())
+        }
+    }
+
+    impl Executor for FakeTxn {
+        async fn fetch_rows(&mut self, query: &str, _params: &HashMap<String, BoltType>) -> Result<Vec<Row>> {
+            self.log.lock().unwrap().push(query.to_string());
+            if self.transient {
+                return Err(Neo4gError::Db(neo4rs::Error::ConnectionError));
+            }
+            Ok(Vec::new())
+        }
+    }
+
+    fn no_backoff(max_retries: u32) -> RetryPolicy {
+        RetryPolicy { max_retries, initial_backoff: Duration::ZERO, max_backoff: Duration::ZERO }
+    }
+
+    #[tokio::test]
+    async fn transient_errors_roll_back_and_rerun_the_closure() {
+        let db = FakeDb { failures: Mutex::new(1), ..Default::default() };
+        let mut calls = 0;
+        let result = retry(&db, &no_backoff(3), async |txn: &mut FakeTxn| {
+            calls += 1;
+            txn.fetch_rows("CREATE (n)", &HashMap::new()).await?;
+            Ok(calls)
+        }).await;
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(*db.log.lock().unwrap(), vec!["begin", "CREATE (n)", "rollback", "begin", "CREATE (n)", "commit"]);
+    }
+
+    #[tokio::test]
+    async fn transient_errors_are_returned_once_the_retries_run_out() {
+        let db = FakeDb { failures: Mutex::new(5), ..Default::default() };
+        let result = retry(&db, &no_backoff(1), async |txn: &mut FakeTxn| txn.fetch_rows("CREATE (n)", &HashMap::new()).await).await;
+        assert!(matches!(result, Err(e) if e.is_transient()));
+        assert_eq!(db.log.lock().unwrap().iter().filter(|entry| *entry == "begin").count(), 2);
+    }
+
+    #[test]
+    fn commit_errors_are_not_transient() {
+        let e = commit_error(Neo4gError::Db(neo4rs::Error::ConnectionError));
+        assert!(!e.is_transient());
+        assert_eq!(e.query(), Some("COMMIT"));
+        assert!(matches!(e, Neo4gError::CommitOutcomeUnknown { source, .. } if matches!(*source, Neo4gError::Db(neo4rs::Error::ConnectionError))));
+    }
+
+    #[test]
+    fn query_errors_before_the_commit_stay_transient() {
+        assert!(Neo4gError::Db(neo4rs::Error::ConnectionError).is_transient());
+    }
+}
@@ -0,0 +1,270 @@
+use quote::quote;
+use syn::{LitInt, LitStr};
+
+/// A vector index declared with #[neo4g(vector(dimensions = 1536, similarity = "cosine"))].
+pub struct VectorAttr {
+    pub dimensions: u32,
+    pub similarity: String,
+}
+
+/// How a field marked #[neo4g(id = ...)] is filled in when an entity is constructed or created.
+pub enum IdStrategy {
+    UuidV4,
+    UuidV7,
+    Ulid,
+    Custom(syn::Path),
+}
+
+impl IdStrategy {
+    /// Returns an expression that generates a new id.
+    pub fn generate(&self) -> proc_macro2::TokenStream {
+        match self {
+            IdStrategy::UuidV4 => quote! { ids::uuid_v4().into() },
+            IdStrategy::UuidV7 => quote! { ids::uuid_v7().into() },
+            IdStrategy::Ulid => quote! { ids::ulid().into() },
+            IdStrategy::Custom(path) => quote! { #path().into() },
+        }
+    }
+}
+
+/// The options set on a field with #[neo4g(...)].
+#[derive(Default)]
+pub struct FieldAttrs {
+    pub unique: bool,
+    pub index: bool,
+    pub not_null: bool,
+    pub node_key: bool,
+    pub fulltext: bool,
+    pub key: bool,
+    pub id: Option<IdStrategy>,
+    pub created_at: bool,
+    pub updated_at: bool,
+    pub soft_delete: bool,
+    pub deleted_at: bool,
+    pub version: bool,
+    pub vector: Option<VectorAttr>,
+}
+
+impl FieldAttrs {
+    /// Returns true if any schema option is set.
+    pub fn has_schema(&self) -> bool {
+        self.unique || self.index || self.not_null || self.node_key || self.fulltext || self.key || self.vector.is_some()
+    }
+}
+
+/// Parses every #[neo4g(...)] attribute on a field.
+pub fn parse_field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("neo4g")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("unique") {
+                attrs.unique = true;
+            } else if meta.path.is_ident("index") {
+                attrs.index = true;
+            } else if meta.path.is_ident("not_null") {
+                attrs.not_null = true;
+            } else if meta.path.is_ident("node_key") {
+                attrs.node_key = true;
+            } else if meta.path.is_ident("fulltext") {
+                attrs.fulltext = true;
+            } else if meta.path.is_ident("key") {
+                attrs.key = true;
+            } else if meta.path.is_ident("created_at") {
+                attrs.created_at = true;
+            } else if meta.path.is_ident("updated_at") {
+                attrs.updated_at = true;
+            } else if meta.path.is_ident("soft_delete") {
+                attrs.soft_delete = true;
+            } else if meta.path.is_ident("deleted_at") {
+                attrs.deleted_at = true;
+            } else if meta.path.is_ident("version") {
+                attrs.version = true;
+            } else if meta.path.is_ident("id") {
+                // Either one of the built in strategies by name, or the path of a fn() that returns the id.
+                let value: syn::Expr = meta.value()?.parse()?;
+                attrs.id = Some(match value {
+                    syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => match lit.value().as_str() {
+                        "uuid_v4" => IdStrategy::UuidV4,
+                        "uuid_v7" => IdStrategy::UuidV7,
+                        "ulid" => IdStrategy::Ulid,
+                        _ => IdStrategy::Custom(lit.parse()?),
+                    },
+                    syn::Expr::Path(path) => IdStrategy::Custom(path.path),
+                    _ => return Err(meta.error("expected \"uuid_v4\", \"uuid_v7\", \"ulid\" or the path of a fn")),
+                });
+            } else if meta.path.is_ident("vector") {
+                let mut dimensions = None;
+                let mut similarity = String::from("cosine");
+                meta.parse_nested_meta(|inner| {
+                    if inner.path.is_ident("dimensions") {
+                        let lit: LitInt = inner.value()?.parse()?;
+                        dimensions = Some(lit.base10_parse::<u32>()?);
+                    } else if inner.path.is_ident("similarity") {
+                        let lit: LitStr = inner.value()?.parse()?;
+                        similarity = lit.value();
+                    } else {
+                        return Err(inner.error("expected `dimensions` or `similarity`"));
+                    }
+                    Ok(())
+                })?;
+                let dimensions = dimensions.ok_or_else(|| meta.error("vector indexes require `dimensions`"))?;
+                attrs.vector = Some(VectorAttr { dimensions, similarity });
+            } else {
+                return Err(meta.error("unsupported neo4g attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(attrs)
+}
+
+/// Parses the #[neo4g(...)] attributes of every field, paired with the field's name.
+/// Fields marked not_query_param are not stored, so they can't take schema, id, timestamp, soft_delete or version attributes.
+pub fn parse_all_field_attrs(fields: &[&syn::Field]) -> syn::Result<Vec<(String, FieldAttrs)>> {
+    let mut field_attrs = Vec::new();
+    for field in fields {
+        let attrs = parse_field_attrs(field)?;
+        let ignored = field.attrs.iter().any(|attr| attr.path().is_ident("not_query_param"));
+        if ignored && attrs.has_schema() {
+            return Err(syn::Error::new_spanned(field, "schema attributes cannot be used on a not_query_param field"));
+        }
+        if ignored && (attrs.id.is_some() || attrs.created_at || attrs.updated_at || attrs.soft_delete || attrs.deleted_at || attrs.version) {
+            return Err(syn::Error::new_spanned(field, "id, timestamp, soft_delete and version attributes cannot be used on a not_query_param field"));
+        }
+        if let Some(ident) = &field.ident {
+            field_attrs.push((ident.to_string(), attrs));
+        }
+    }
+    Ok(field_attrs)
+}
+
+/// The options set on a struct with #[neo4g(...)].
+#[derive(Default)]
+pub struct StructAttrs {
+    pub repository: bool,
+    pub before_save: bool,
+    pub after_load: bool,
+    pub before_delete: bool,
+}
+
+impl StructAttrs {
+    /// Returns the Neo4gEntity hook methods that call the hook traits chosen with #[neo4g(hooks)].
+    pub fn hook_fns(&self) -> proc_macro2::TokenStream {
+        let before_save = self.before_save.then(|| quote! {
+            fn hook_before_save(&mut self) {
+                <Self as BeforeSave>::before_save(self);
+            }
+        });
+        let after_load = self.after_load.then(|| quote! {
+            fn hook_after_load(&mut self) {
+                <Self as AfterLoad>::after_load(self);
+            }
+        });
+        let before_delete = self.before_delete.then(|| quote! {
+            fn hook_before_delete(&self) {
+                <Self as BeforeDelete>::before_delete(self);
+            }
+        });
+        quote! { #before_save #after_load #before_delete }
+    }
+}
+
+/// Returns the id strategy of a field marked #[neo4g(id = ...)]. Invalid attributes are reported by parse_field_attrs.
+pub fn id_strategy(field: &syn::Field) -> Option<IdStrategy> {
+    parse_field_attrs(field).ok().and_then(|attrs| attrs.id)
+}
+
+/// Returns the value a constructor fills in for a field it takes no parameter for: a new id, the current time for managed timestamps, or the first version.
+pub fn generated_value(field: &syn::Field) -> Option<proc_macro2::TokenStream> {
+    let attrs = parse_field_attrs(field).ok()?;
+    if let Some(strategy) = attrs.id {
+        return Some(strategy.generate());
+    }
+    if attrs.version {
+        return Some(quote! { 0 });
+    }
+    if attrs.created_at || attrs.updated_at {
+        return Some(if is_naive_datetime(&field.ty) {
+            quote! { chrono::Utc::now().naive_local() }
+        } else {
+            quote! { chrono::Utc::now().into() }
+        });
+    }
+    None
+}
+
+/// Returns the Cypher function that sets a managed timestamp field on the server, matching the field's type.
+pub fn server_time_fn(ty: &syn::Type) -> &'static str {
+    if is_naive_datetime(ty) {
+        "localdatetime()"
+    } else {
+        "datetime()"
+    }
+}
+
+fn is_naive_datetime(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => match type_path.path.segments.last() {
+            Some(seg) if seg.ident == "NaiveDateTime" => true,
+            // Looks inside Option<NaiveDateTime>, as used for deleted_at fields.
+            Some(seg) if seg.ident == "Option" => match &seg.arguments {
+                syn::PathArguments::AngleBracketed(args) => args.args.iter().any(|arg| matches!(arg, syn::GenericArgument::Type(inner) if is_naive_datetime(inner))),
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns the fields that identify an entity: the fields marked key, or else the node_key fields, or else the first unique field.
+pub fn key_fields(field_attrs: &[(String, FieldAttrs)]) -> Vec<String> {
+    let marked = |f: fn(&FieldAttrs) -> bool| -> Vec<String> {
+        field_attrs.iter().filter(|(_, attrs)| f(attrs)).map(|(name, _)| name.clone()).collect()
+    };
+    let key = marked(|attrs| attrs.key);
+    if !key.is_empty() {
+        return key;
+    }
+    let node_key = marked(|attrs| attrs.node_key);
+    if !node_key.is_empty() {
+        return node_key;
+    }
+    marked(|attrs| attrs.unique).into_iter().take(1).collect()
+}
+
+/// Parses every #[neo4g(...)] attribute on a struct.
+pub fn parse_struct_attrs(attrs: &[syn::Attribute]) -> syn::Result<StructAttrs> {
+    let mut struct_attrs = StructAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("neo4g")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("repository") {
+                struct_attrs.repository = true;
+            } else if meta.path.is_ident("hooks") {
+                // Either every hook, or the hooks listed in parentheses.
+                if meta.input.peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|hook| {
+                        if hook.path.is_ident("before_save") {
+                            struct_attrs.before_save = true;
+                        } else if hook.path.is_ident("after_load") {
+                            struct_attrs.after_load = true;
+                        } else if hook.path.is_ident("before_delete") {
+                            struct_attrs.before_delete = true;
+                        } else {
+                            return Err(hook.error("expected before_save, after_load or before_delete"));
+                        }
+                        Ok(())
+                    })?;
+                } else {
+                    struct_attrs.before_save = true;
+                    struct_attrs.after_load = true;
+                    struct_attrs.before_delete = true;
+                }
+            } else {
+                return Err(meta.error("unsupported neo4g attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(struct_attrs)
+}
@@ -0,0 +1,535 @@
+use std::collections::{HashMap, HashSet};
+
+use heck::{ToPascalCase, ToShoutySnakeCase};
+use proc_macro::TokenStream;
+use proc_macro2::{Delimiter, Ident, Spacing, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, quote_spanned};
+
+/// Keywords that are followed by a space rather than glued to a following paren group.
+const KEYWORDS: &[&str] = &[
+    "MATCH", "OPTIONAL", "WHERE", "AND", "OR", "XOR", "NOT", "RETURN", "WITH", "CREATE", "MERGE",
+    "SET", "DELETE", "DETACH", "REMOVE", "UNWIND", "AS", "IN", "CALL", "ON", "ORDER", "BY",
+    "SKIP", "LIMIT", "DISTINCT", "UNION", "ALL", "IS", "NULL", "CASE", "WHEN", "THEN", "ELSE",
+    "END", "ASC", "DESC", "EXISTS", "STARTS", "ENDS", "CONTAINS",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum EntityKind {
+    Node,
+    Relation,
+}
+
+/// A token after joint punctuation has been merged, e.g. `-` `>` becomes `->`.
+enum Token {
+    Tree(TokenTree),
+    Op(String, proc_macro2::Span),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Prev {
+    Start,
+    Keyword,
+    Word,
+    Pattern,
+    Arrow,
+    /// A `.` or `:`, after which a word is a property or label name rather than a keyword.
+    Accessor,
+    Other,
+}
+
+struct CypherBuilder {
+    bindings: HashMap<String, (Ident, EntityKind)>,
+    checks: Vec<TokenStream2>,
+    params: Vec<TokenStream2>,
+    param_names: HashSet<String>,
+}
+
+pub fn generate_cypher(input: TokenStream) -> TokenStream {
+    let (builder, query) = match parse_query(input.into()) {
+        Ok(parsed) => parsed,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let mut bindings: Vec<_> = builder.bindings.iter().collect();
+    bindings.sort_by(|a, b| a.0.cmp(b.0));
+    let check_args: Vec<_> = bindings.iter().map(|(alias, (ty, _))| {
+        let alias = format_ident!("{}", alias);
+        quote! { #alias: &#ty }
+    }).collect();
+    let checks = &builder.checks;
+    let params = &builder.params;
+
+    let expanded = quote! {
+        {
+            #[allow(unused, non_snake_case)]
+            fn __neo4g_cypher_check(#(#check_args),*) {
+                #(#checks)*
+            }
+            let mut __neo4g_params: ::std::collections::HashMap<::std::string::String, ::neo4g::prelude::BoltType> = ::std::collections::HashMap::new();
+            #(#params)*
+            (::std::string::String::from(#query), __neo4g_params)
+        }
+    };
+    expanded.into()
+}
+
+/// Parses the macro input into the query string, and the checks and params to expand alongside it.
+fn parse_query(input: TokenStream2) -> syn::Result<(CypherBuilder, String)> {
+    let mut builder = CypherBuilder {
+        bindings: HashMap::new(),
+        checks: Vec::new(),
+        params: Vec::new(),
+        param_names: HashSet::new(),
+    };
+    let tokens = merge_ops(input);
+    builder.collect_bindings(&tokens)?;
+    let query = builder.render(&tokens)?;
+    Ok((builder, query))
+}
+
+/// Merges runs of joint punctuation into single operator tokens.
+fn merge_ops(input: TokenStream2) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut span = proc_macro2::Span::call_site();
+    for tree in input {
+        match tree {
+            TokenTree::Punct(ref punct) if punct.as_char() != '$' => {
+                if current.is_empty() {
+                    span = punct.span();
+                }
+                current.push(punct.as_char());
+                if punct.spacing() == Spacing::Alone {
+                    tokens.push(Token::Op(std::mem::take(&mut current), span));
+                }
+            },
+            other => {
+                if !current.is_empty() {
+                    tokens.push(Token::Op(std::mem::take(&mut current), span));
+                }
+                tokens.push(Token::Tree(other));
+            },
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(Token::Op(current, span));
+    }
+    tokens
+}
+
+fn is_keyword(ident: &Ident) -> bool {
+    let upper = ident.to_string().to_uppercase();
+    KEYWORDS.contains(&upper.as_str())
+}
+
+fn is_arrow(op: &str) -> bool {
+    matches!(op, "-" | "->" | "<-" | "--" | "<--" | "-->")
+}
+
+/// Splits the contents of a node pattern into its alias, labels and props, or None if it isn't a node pattern.
+fn split_node(stream: &TokenStream2) -> Option<(Option<Ident>, Vec<Ident>, Option<TokenStream2>)> {
+    let tokens: Vec<Token> = merge_ops(stream.clone());
+    let mut alias = None;
+    let mut labels = Vec::new();
+    let mut props = None;
+    let mut i = 0;
+    if let Some(Token::Tree(TokenTree::Ident(ident))) = tokens.get(i) {
+        alias = Some(ident.clone());
+        i += 1;
+    }
+    while let Some(Token::Op(op, _)) = tokens.get(i) {
+        if op != ":" {
+            return None;
+        }
+        match tokens.get(i + 1) {
+            Some(Token::Tree(TokenTree::Ident(label))) => labels.push(label.clone()),
+            _ => return None,
+        }
+        i += 2;
+    }
+    if let Some(Token::Tree(TokenTree::Group(group))) = tokens.get(i) {
+        if group.delimiter() != Delimiter::Brace {
+            return None;
+        }
+        props = Some(group.stream());
+        i += 1;
+    }
+    if i == tokens.len() {
+        Some((alias, labels, props))
+    } else {
+        None
+    }
+}
+
+/// Splits the contents of a relation pattern into its alias, types, the remaining tokens (eg. *1..3) and props.
+fn split_relation(stream: &TokenStream2) -> (Option<Ident>, Vec<Ident>, TokenStream2, Option<TokenStream2>) {
+    let trees: Vec<TokenTree> = stream.clone().into_iter().collect();
+    let mut alias = None;
+    let mut types = Vec::new();
+    let mut i = 0;
+    if let Some(TokenTree::Ident(ident)) = trees.get(i) {
+        alias = Some(ident.clone());
+        i += 1;
+    }
+    if let Some(TokenTree::Punct(p)) = trees.get(i) {
+        if p.as_char() == ':' {
+            i += 1;
+            while let Some(TokenTree::Ident(ty)) = trees.get(i) {
+                types.push(ty.clone());
+                i += 1;
+                match trees.get(i) {
+                    Some(TokenTree::Punct(p)) if p.as_char() == '|' => i += 1,
+                    _ => break,
+                }
+            }
+        }
+    }
+    let mut props = None;
+    let mut rest: Vec<TokenTree> = trees[i..].to_vec();
+    if let Some(TokenTree::Group(group)) = rest.last() {
+        if group.delimiter() == Delimiter::Brace {
+            props = Some(group.stream());
+            rest.pop();
+        }
+    }
+    (alias, types, rest.into_iter().collect(), props)
+}
+
+impl CypherBuilder {
+    /// Walks every pattern in the query and records which Neo4g type each alias is bound to.
+    fn collect_bindings(&mut self, tokens: &[Token]) -> syn::Result<()> {
+        let mut prev_op = false;
+        for token in tokens {
+            if let Token::Tree(TokenTree::Group(group)) = token {
+                match group.delimiter() {
+                    Delimiter::Parenthesis => {
+                        if let Some((Some(alias), labels, _)) = split_node(&group.stream()) {
+                            if let Some(label) = labels.first() {
+                                self.bind(&alias, label, EntityKind::Node)?;
+                            }
+                        } else {
+                            self.collect_bindings(&merge_ops(group.stream()))?;
+                        }
+                    },
+                    Delimiter::Bracket if prev_op => {
+                        let (alias, types, _, _) = split_relation(&group.stream());
+                        if let (Some(alias), Some(ty)) = (alias, types.first()) {
+                            self.bind(&alias, ty, EntityKind::Relation)?;
+                        }
+                    },
+                    _ => self.collect_bindings(&merge_ops(group.stream()))?,
+                }
+            }
+            prev_op = matches!(token, Token::Op(op, _) if op.ends_with('-'));
+        }
+        Ok(())
+    }
+
+    fn bind(&mut self, alias: &Ident, ty: &Ident, kind: EntityKind) -> syn::Result<()> {
+        let name = alias.to_string();
+        if let Some((existing, _)) = self.bindings.get(&name) {
+            if existing != ty {
+                return Err(syn::Error::new(alias.span(), format!("alias '{}' is already bound to {}", name, existing)));
+            }
+        }
+        self.bindings.insert(name, (ty.clone(), kind));
+        Ok(())
+    }
+
+    fn render(&mut self, tokens: &[Token]) -> syn::Result<String> {
+        let mut out = String::new();
+        let mut prev = Prev::Start;
+        let mut i = 0;
+        while i < tokens.len() {
+            match &tokens[i] {
+                Token::Op(op, span) => {
+                    let next_is_pattern = matches!(tokens.get(i + 1), Some(Token::Tree(TokenTree::Group(g))) if g.delimiter() != Delimiter::Brace);
+                    if is_arrow(op) && (prev == Prev::Pattern || prev == Prev::Arrow || next_is_pattern) {
+                        out.push_str(op);
+                        prev = Prev::Arrow;
+                    } else if op == "," {
+                        out.push_str(", ");
+                        prev = Prev::Start;
+                    } else if op == "." {
+                        out.push('.');
+                        prev = Prev::Accessor;
+                    } else if op == ":" {
+                        out.push_str(": ");
+                        prev = Prev::Accessor;
+                    } else if op == ";" {
+                        return Err(syn::Error::new(*span, "cypher! accepts a single query"));
+                    } else {
+                        push_spaced(&mut out, op);
+                        out.push(' ');
+                        prev = Prev::Start;
+                    }
+                },
+                Token::Tree(TokenTree::Punct(_)) => {
+                    // Only $ reaches here, as every other punct is merged into an Op.
+                    match tokens.get(i + 1) {
+                        Some(Token::Tree(TokenTree::Ident(name))) if !(is_keyword(name) && name.to_string() == name.to_string().to_uppercase()) => {
+                            self.untyped_param(name);
+                            push_spaced(&mut out, &format!("${}", name));
+                            prev = Prev::Word;
+                            i += 1;
+                        },
+                        _ => return Err(syn::Error::new(proc_macro2::Span::call_site(), "expected a variable name after $")),
+                    }
+                },
+                Token::Tree(TokenTree::Ident(ident)) => {
+                    // Property names and map keys such as u.end or {end: 1} are kept as written.
+                    let is_key = matches!(tokens.get(i + 1), Some(Token::Op(op, _)) if op == ":");
+                    if is_keyword(ident) && prev != Prev::Accessor && !is_key {
+                        push_spaced(&mut out, &ident.to_string().to_uppercase());
+                        prev = Prev::Keyword;
+                    } else {
+                        if let (Some(Token::Op(dot, _)), Some(Token::Tree(TokenTree::Ident(prop)))) = (tokens.get(i + 1), tokens.get(i + 2)) {
+                            if dot == "." && self.bindings.contains_key(&ident.to_string()) {
+                                self.check_prop(ident, prop);
+                            }
+                        }
+                        push_spaced(&mut out, &ident.to_string());
+                        prev = Prev::Word;
+                    }
+                },
+                Token::Tree(TokenTree::Literal(lit)) => {
+                    push_spaced(&mut out, &lit.to_string());
+                    prev = Prev::Word;
+                },
+                Token::Tree(TokenTree::Group(group)) => {
+                    match group.delimiter() {
+                        Delimiter::Parenthesis => {
+                            let node = if prev == Prev::Word { None } else { split_node(&group.stream()) };
+                            if let Some((alias, labels, props)) = node {
+                                let pattern = self.render_node(alias, labels, props)?;
+                                if prev == Prev::Arrow { out.push_str(&pattern) } else { push_spaced(&mut out, &pattern) }
+                                prev = Prev::Pattern;
+                            } else {
+                                let inner = self.render(&merge_ops(group.stream()))?;
+                                if prev == Prev::Word { out.push('(') } else { push_spaced(&mut out, "(") }
+                                out.push_str(&inner);
+                                out.push(')');
+                                prev = Prev::Word;
+                            }
+                        },
+                        Delimiter::Bracket if prev == Prev::Arrow => {
+                            let pattern = self.render_relation(&group.stream())?;
+                            out.push_str(&pattern);
+                            prev = Prev::Pattern;
+                        },
+                        Delimiter::Bracket => {
+                            let inner = self.render(&merge_ops(group.stream()))?;
+                            push_spaced(&mut out, &format!("[{}]", inner));
+                            prev = Prev::Word;
+                        },
+                        Delimiter::Brace => {
+                            let inner = self.render(&merge_ops(group.stream()))?;
+                            push_spaced(&mut out, &format!("{{{}}}", inner));
+                            prev = Prev::Other;
+                        },
+                        Delimiter::None => {
+                            let inner = self.render(&merge_ops(group.stream()))?;
+                            push_spaced(&mut out, &inner);
+                            prev = Prev::Word;
+                        },
+                    }
+                },
+            }
+            i += 1;
+        }
+        Ok(out.trim_end().to_string())
+    }
+
+    fn render_node(&mut self, alias: Option<Ident>, labels: Vec<Ident>, props: Option<TokenStream2>) -> syn::Result<String> {
+        let mut pattern = String::from("(");
+        if let Some(alias) = &alias {
+            pattern.push_str(&alias.to_string());
+        }
+        for (n, label) in labels.iter().enumerate() {
+            if n == 0 {
+                self.checks.push(quote! { let _ = #label::get_node_label; });
+            } else {
+                self.checks.push(quote! { let _ = Label::#label; });
+            }
+            pattern.push(':');
+            pattern.push_str(&label.to_string());
+        }
+        if let Some(props) = props {
+            let ty = labels.first().cloned().or_else(|| alias.as_ref().and_then(|a| self.bindings.get(&a.to_string()).map(|(ty, _)| ty.clone())));
+            pattern.push_str(&format!(" {{{}}}", self.render_props(alias.as_ref(), ty.as_ref(), props)?));
+        }
+        pattern.push(')');
+        Ok(pattern)
+    }
+
+    fn render_relation(&mut self, stream: &TokenStream2) -> syn::Result<String> {
+        let (alias, types, rest, props) = split_relation(stream);
+        let mut pattern = String::from("[");
+        if let Some(alias) = &alias {
+            pattern.push_str(&alias.to_string());
+        }
+        let rel_types: Vec<String> = types.iter().map(|ty| {
+            self.checks.push(quote! { let _ = #ty::get_relation_label; });
+            ty.to_string().to_shouty_snake_case()
+        }).collect();
+        if !rel_types.is_empty() {
+            pattern.push(':');
+            pattern.push_str(&rel_types.join("|"));
+        }
+        if !rest.is_empty() {
+            pattern.push_str(&rest.to_string().replace(' ', ""));
+        }
+        if let Some(props) = props {
+            let ty = types.first().cloned().or_else(|| alias.as_ref().and_then(|a| self.bindings.get(&a.to_string()).map(|(ty, _)| ty.clone())));
+            pattern.push_str(&format!(" {{{}}}", self.render_props(alias.as_ref(), ty.as_ref(), props)?));
+        }
+        pattern.push(']');
+        Ok(pattern)
+    }
+
+    /// Renders a props map, checking each key against the entity's fields.
+    /// Params used as values are converted through the entity's Props enum, so their types are checked too.
+    fn render_props(&mut self, alias: Option<&Ident>, ty: Option<&Ident>, props: TokenStream2) -> syn::Result<String> {
+        let tokens = merge_ops(props);
+        let mut entries = Vec::new();
+        for entry in tokens.split(|t| matches!(t, Token::Op(op, _) if op == ",")) {
+            if entry.is_empty() {
+                continue;
+            }
+            let key = match (entry.first(), entry.get(1)) {
+                (Some(Token::Tree(TokenTree::Ident(key))), Some(Token::Op(colon, _))) if colon == ":" => key.clone(),
+                _ => return Err(syn::Error::new(proc_macro2::Span::call_site(), "expected `property: value` in a pattern's props")),
+            };
+            if let Some(ty) = ty {
+                let field_check = match alias {
+                    Some(alias) => quote! { let _ = &#alias.#key; },
+                    None => quote! { let _ = |entity: &#ty| { let _ = &entity.#key; }; },
+                };
+                self.checks.push(field_check);
+            }
+            let value = &entry[2..];
+            let rendered = match (value.first(), value.get(1), ty) {
+                (Some(Token::Tree(TokenTree::Punct(_))), Some(Token::Tree(TokenTree::Ident(name))), Some(ty)) if value.len() == 2 => {
+                    self.typed_param(ty, &key, name);
+                    format!("${}", name)
+                },
+                _ => self.render(value)?,
+            };
+            entries.push(format!("{}: {}", key, rendered));
+        }
+        Ok(entries.join(", "))
+    }
+
+    fn check_prop(&mut self, alias: &Ident, prop: &Ident) {
+        self.checks.push(quote! { let _ = &#alias.#prop; });
+    }
+
+    fn typed_param(&mut self, ty: &Ident, key: &Ident, name: &Ident) {
+        let name_str = name.to_string();
+        if !self.param_names.insert(name_str.clone()) {
+            return;
+        }
+        let variant = format_ident!("{}", key.to_string().to_pascal_case());
+        self.params.push(quote_spanned! {name.span()=>
+            __neo4g_params.insert(#name_str.to_string(), ::neo4g::traits::QueryParam::to_query_param(&<#ty as ::neo4g::traits::Neo4gEntity>::Props::#variant(#name.clone())).1);
+        });
+    }
+
+    fn untyped_param(&mut self, name: &Ident) {
+        let name_str = name.to_string();
+        if !self.param_names.insert(name_str.clone()) {
+            return;
+        }
+        self.params.push(quote! {
+            __neo4g_params.insert(#name_str.to_string(), ::neo4g::prelude::BoltType::from(#name.clone()));
+        });
+    }
+}
+
+fn push_spaced(out: &mut String, s: &str) {
+    if !out.is_empty() && !out.ends_with([' ', '(', '[', '.']) {
+        out.push(' ');
+    }
+    out.push_str(s);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(input: &str) -> String {
+        parse_query(input.parse().unwrap()).map(|(_, query)| query).unwrap()
+    }
+
+    fn error(input: &str) -> String {
+        match parse_query(input.parse().unwrap()) {
+            Ok((_, query)) => panic!("expected an error, got {}", query),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn renders_node_and_relation_patterns() {
+        assert_eq!(
+            query("MATCH (u: User {name: $name})-[m: MemberOf]->(g: Group) RETURN u, g"),
+            "MATCH (u:User {name: $name})-[m:MEMBER_OF]->(g:Group) RETURN u, g",
+        );
+    }
+
+    #[test]
+    fn renders_incoming_relations_and_hops() {
+        assert_eq!(
+            query("MATCH (g: Group)<-[m: MemberOf *1..3]-(u: User) RETURN g"),
+            "MATCH (g:Group)<-[m:MEMBER_OF*1..3]-(u:User) RETURN g",
+        );
+    }
+
+    #[test]
+    fn uppercases_keywords_and_keeps_function_calls_together() {
+        assert_eq!(
+            query("match (u: User) where u.name starts with $prefix return count(u)"),
+            "MATCH (u:User) WHERE u.name STARTS WITH $prefix RETURN count(u)",
+        );
+    }
+
+    #[test]
+    fn keeps_property_names_and_map_keys_that_are_keywords() {
+        assert_eq!(
+            query("MATCH (u: User) WHERE u.end > $from RETURN {end: u.end, set: u.match}"),
+            "MATCH (u:User) WHERE u.end > $from RETURN {end: u.end, set: u.match}",
+        );
+    }
+
+    #[test]
+    fn binds_aliases_to_their_types() {
+        let (builder, _) = parse_query("MATCH (u: User)-[m: MemberOf]->(g: Group) RETURN u".parse().unwrap()).unwrap();
+        let mut aliases: Vec<(String, String)> = builder.bindings.iter().map(|(alias, (ty, _))| (alias.clone(), ty.to_string())).collect();
+        aliases.sort();
+        assert_eq!(aliases, vec![
+            ("g".to_string(), "Group".to_string()),
+            ("m".to_string(), "MemberOf".to_string()),
+            ("u".to_string(), "User".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn collects_each_param_once() {
+        let (builder, _) = parse_query("MATCH (u: User {name: $name}) WHERE u.forename = $name OR u.surname = $surname RETURN u".parse().unwrap()).unwrap();
+        assert_eq!(builder.params.len(), 2);
+    }
+
+    #[test]
+    fn rejects_more_than_one_query() {
+        assert_eq!(error("MATCH (u: User) RETURN u; MATCH (g: Group) RETURN g"), "cypher! accepts a single query");
+    }
+
+    #[test]
+    fn rejects_an_alias_bound_to_two_types() {
+        assert_eq!(error("MATCH (u: User), (u: Group) RETURN u"), "alias 'u' is already bound to User");
+    }
+
+    #[test]
+    fn rejects_a_dollar_without_a_name() {
+        assert_eq!(error("MATCH (u: User) WHERE u.name = $ RETURN u"), "expected a variable name after $");
+    }
+}
@@ -27,6 +27,8 @@ pub fn generate_entity_wrapper(input: TokenStream) -> TokenStream {
     let mut db_from_relation_checks = Vec::new();
     let mut call_get_uuid_arms = Vec::new();
     let mut paramable_arms = Vec::new();
+    let mut soft_delete_arms = Vec::new();
+    let mut before_delete_arms = Vec::new();
 
     for variant in data_enum.variants.iter() {
         let var_name = &variant.ident;
@@ -82,6 +84,14 @@ pub fn generate_entity_wrapper(input: TokenStream) -> TokenStream {
                 #enum_name::#var_name(inner) => inner.get_entity_type(),
             };
             call_get_entity_type_arms.push(call_get_entity_type_arm);
+            let soft_delete_arm = quote! {
+                #enum_name::#var_name(inner) => inner.soft_delete_sets(alias),
+            };
+            soft_delete_arms.push(soft_delete_arm);
+            let before_delete_arm = quote! {
+                #enum_name::#var_name(inner) => inner.hook_before_delete(),
+            };
+            before_delete_arms.push(before_delete_arm);
             let dbcheck = quote! {
                 if labels.contains(&#var_name_str) {
                     return #var_name::from_db_entity(db_entity);
@@ -155,6 +165,24 @@ pub fn generate_entity_wrapper(input: TokenStream) -> TokenStream {
         }
     };
 
+    let soft_delete_sets_fn = quote! {
+        fn soft_delete_sets(&self, alias: &str) -> Vec<String> {
+            match self {
+                #(#soft_delete_arms)*
+                _ => Vec::new(),
+            }
+        }
+    };
+
+    let before_delete_fn = quote! {
+        fn hook_before_delete(&self) {
+            match self {
+                #(#before_delete_arms)*
+                _ => (),
+            }
+        }
+    };
+
     let from_db_entity_fn = quote! {
         fn from_db_entity(db_entity: DbEntityWrapper) -> Self {
             match db_entity.clone() {
@@ -168,6 +196,7 @@ pub fn generate_entity_wrapper(input: TokenStream) -> TokenStream {
                     #(#db_from_relation_checks)*
                     return #enum_name::Nothing(Nothing::new(true));
                },
+                DbEntityWrapper::Null => #enum_name::Nothing(Nothing::new(true)),
             }
         }
     };
@@ -188,6 +217,8 @@ pub fn generate_entity_wrapper(input: TokenStream) -> TokenStream {
         impl WrappedNeo4gEntity for EntityWrapper {
             #from_db_entity_fn
             #get_entity_type_fn
+            #soft_delete_sets_fn
+            #before_delete_fn
         }
         
         impl PartialEq for #enum_name {
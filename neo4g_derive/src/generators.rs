@@ -1,5 +1,7 @@
+use heck::ToPascalCase;
 use quote::quote;
 use syn::Ident;
+use crate::attributes::{self, FieldAttrs};
 
 pub fn generate_get_node_entity_type() -> proc_macro2::TokenStream {
     quote! {
@@ -42,7 +44,7 @@ pub fn generate_create_node_from_self(struct_name: &Ident, struct_name_str: &str
 pub fn generate_node_by(struct_name: &Ident, struct_name_str: &str, props_enum_name: &Ident) -> proc_macro2::TokenStream {
     quote! {
         pub fn node_by(alias: &str, props: &[#props_enum_name]) -> (String, std::collections::HashMap<String, BoltType>) {
-            let mut query = format!("({}:{}:AdditionalLabels {{", alias, #struct_name_str);
+            let mut query = format!("({}:{} {{", alias, #struct_name_str);
             let mut params = std::collections::HashMap::new();
 
             let props_str: Vec<String> = props
@@ -120,4 +122,215 @@ pub fn generate_relation_by(struct_name: &Ident, struct_name_str: &str, props_en
             (query, params)
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn generate_schema(label_fn: &Ident, entity_type: &proc_macro2::TokenStream, field_attrs: &[(String, FieldAttrs)]) -> proc_macro2::TokenStream {
+    let mut items = Vec::new();
+    let mut node_key = Vec::new();
+    let mut fulltext = Vec::new();
+    let mut key = Vec::new();
+    for (name, attrs) in field_attrs {
+        if attrs.unique {
+            items.push(quote! { ::neo4g::schema::SchemaItem::Unique(#name.to_string()) });
+        }
+        if attrs.not_null {
+            items.push(quote! { ::neo4g::schema::SchemaItem::NotNull(#name.to_string()) });
+        }
+        if attrs.index {
+            items.push(quote! { ::neo4g::schema::SchemaItem::Index(#name.to_string()) });
+        }
+        if let Some(vector) = &attrs.vector {
+            let dimensions = vector.dimensions;
+            let similarity = &vector.similarity;
+            items.push(quote! { ::neo4g::schema::SchemaItem::Vector { property: #name.to_string(), dimensions: #dimensions, similarity: #similarity.to_string() } });
+        }
+        if attrs.node_key {
+            node_key.push(quote! { #name.to_string() });
+        }
+        if attrs.fulltext {
+            fulltext.push(quote! { #name.to_string() });
+        }
+        if attrs.key {
+            key.push((name, attrs.unique));
+        }
+    }
+    // A single key field is made unique, unless it already is. A composite key is made unique as a whole.
+    match key.as_slice() {
+        [] => {},
+        [(name, unique)] => {
+            if !*unique {
+                items.push(quote! { ::neo4g::schema::SchemaItem::Unique(#name.to_string()) });
+            }
+        },
+        _ => {
+            let names = key.iter().map(|(name, _)| quote! { #name.to_string() });
+            items.push(quote! { ::neo4g::schema::SchemaItem::CompositeUnique(vec![#(#names),*]) });
+        },
+    }
+    if !node_key.is_empty() {
+        items.push(quote! { ::neo4g::schema::SchemaItem::NodeKey(vec![#(#node_key),*]) });
+    }
+    if !fulltext.is_empty() {
+        items.push(quote! { ::neo4g::schema::SchemaItem::Fulltext(vec![#(#fulltext),*]) });
+    }
+    quote! {
+        pub fn schema() -> ::neo4g::schema::EntitySchema {
+            ::neo4g::schema::EntitySchema::new(Self::#label_fn(), #entity_type, vec![#(#items),*])
+        }
+    }
+}
+
+/// Generates the entity methods for fields whose values are managed for the user:
+/// created_at_prop, updated_at_prop, deleted_at_prop, soft_delete_prop, version_prop and ensure_id.
+/// Each of these can be marked on one field at most.
+pub fn generate_managed_field_fns(props_enum_name: &Ident, fields: &[&syn::Field]) -> syn::Result<proc_macro2::TokenStream> {
+    let marked = |is_marked: fn(&FieldAttrs) -> bool| -> Vec<&syn::Field> {
+        fields.iter().copied().filter(|field| attributes::parse_field_attrs(field).map(|attrs| is_marked(&attrs)).unwrap_or(false)).collect()
+    };
+    let only_one = |fields: &[&syn::Field], message: String| match fields.get(1) {
+        Some(field) => Err(syn::Error::new_spanned(field, message)),
+        None => Ok(()),
+    };
+
+    // created_at_prop, updated_at_prop and deleted_at_prop for the fields marked #[neo4g(created_at)], #[neo4g(updated_at)] and #[neo4g(deleted_at)].
+    let mut timestamp_fns = Vec::new();
+    for (fn_name, is_marked) in [("created_at_prop", (|attrs: &FieldAttrs| attrs.created_at) as fn(&FieldAttrs) -> bool), ("updated_at_prop", |attrs| attrs.updated_at), ("deleted_at_prop", |attrs| attrs.deleted_at)] {
+        let fn_ident = Ident::new(fn_name, props_enum_name.span());
+        let marked = marked(is_marked);
+        only_one(&marked, format!("only one field can be marked for {}", fn_name))?;
+        if let Some(field) = marked.first() {
+            let name = field.ident.as_ref().unwrap().to_string();
+            let function = attributes::server_time_fn(&field.ty);
+            timestamp_fns.push(quote! {
+                fn #fn_ident(&self) -> Option<(&'static str, &'static str)> {
+                    Some((#name, #function))
+                }
+            });
+        }
+    }
+
+    // soft_delete_prop for the field marked #[neo4g(soft_delete)].
+    let soft_delete_fields = marked(|attrs| attrs.soft_delete);
+    only_one(&soft_delete_fields, "only one field can be marked soft_delete".to_string())?;
+    let soft_delete_fn = match soft_delete_fields.first() {
+        Some(field) => {
+            let field_ident = field.ident.as_ref().unwrap();
+            let variant = Ident::new(&field_ident.to_string().to_pascal_case(), field_ident.span());
+            quote! {
+                fn soft_delete_prop(&self, deleted: bool) -> Option<Self::Props> {
+                    Some(#props_enum_name::#variant(deleted))
+                }
+            }
+        },
+        None => quote! {},
+    };
+
+    // version_prop for the field marked #[neo4g(version)].
+    let version_fields = marked(|attrs| attrs.version);
+    only_one(&version_fields, "only one field can be marked version".to_string())?;
+    let version_fn = match version_fields.first() {
+        Some(field) => {
+            let field_ident = field.ident.as_ref().unwrap();
+            quote! {
+                fn version_prop(&self) -> Option<Self::Props> {
+                    Some(self.#field_ident.clone())
+                }
+            }
+        },
+        None => quote! {},
+    };
+
+    // ensure_id for the field marked #[neo4g(id = ...)], so create paths fill in ids that were left empty.
+    let id_fields: Vec<&syn::Field> = fields.iter().copied().filter(|field| attributes::id_strategy(field).is_some()).collect();
+    only_one(&id_fields, "only one field can be marked #[neo4g(id = ...)]".to_string())?;
+    let ensure_id_fn = match id_fields.first() {
+        Some(field) => {
+            let field_ident = field.ident.as_ref().unwrap();
+            let field_ty = &field.ty;
+            let variant = Ident::new(&field_ident.to_string().to_pascal_case(), field_ident.span());
+            let generated = attributes::id_strategy(field).map(|strategy| strategy.generate());
+            quote! {
+                fn ensure_id(&mut self) {
+                    if let #props_enum_name::#variant(value) = &mut self.#field_ident {
+                        if *value == <#field_ty as Default>::default() {
+                            *value = #generated;
+                        }
+                    }
+                }
+            }
+        },
+        None => quote! {},
+    };
+
+    Ok(quote! {
+        #ensure_id_fn
+
+        #(#timestamp_fns)*
+
+        #soft_delete_fn
+
+        #version_fn
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(input: syn::FieldsNamed) -> Vec<syn::Field> {
+        input.named.into_iter().collect()
+    }
+
+    fn managed_fns(fields: &[syn::Field]) -> syn::Result<String> {
+        let fields: Vec<&syn::Field> = fields.iter().collect();
+        generate_managed_field_fns(&Ident::new("PageProps", proc_macro2::Span::call_site()), &fields).map(|fns| fns.to_string())
+    }
+
+    #[test]
+    fn managed_fields_generate_their_methods() {
+        let fields = fields(syn::parse_quote!({
+            #[neo4g(id = "uuid_v4")]
+            id: String,
+            #[neo4g(updated_at)]
+            updated: NaiveDateTime,
+            #[neo4g(soft_delete)]
+            deleted: bool,
+            #[neo4g(version)]
+            version: i64,
+        }));
+        let fns = managed_fns(&fields).unwrap();
+        for name in ["fn ensure_id", "fn updated_at_prop", "fn soft_delete_prop", "fn version_prop"] {
+            assert!(fns.contains(name), "{} missing from {}", name, fns);
+        }
+        assert!(!fns.contains("fn created_at_prop"), "{}", fns);
+        assert!(fns.contains("PageProps :: Deleted (deleted)"), "{}", fns);
+    }
+
+    #[test]
+    fn managed_attributes_can_only_be_marked_on_one_field() {
+        let fields = fields(syn::parse_quote!({
+            #[neo4g(soft_delete)]
+            deleted: bool,
+            #[neo4g(soft_delete)]
+            removed: bool,
+        }));
+        assert_eq!(managed_fns(&fields).unwrap_err().to_string(), "only one field can be marked soft_delete");
+    }
+
+    #[test]
+    fn not_query_param_fields_cannot_take_managed_or_schema_attributes() {
+        let managed = fields(syn::parse_quote!({
+            #[not_query_param]
+            #[neo4g(version)]
+            version: i64,
+        }));
+        let schema = fields(syn::parse_quote!({
+            #[not_query_param]
+            #[neo4g(unique)]
+            name: String,
+        }));
+        let parse = |fields: &[syn::Field]| attributes::parse_all_field_attrs(&fields.iter().collect::<Vec<_>>()).map(|attrs| attrs.len());
+        assert_eq!(parse(&managed).unwrap_err().to_string(), "id, timestamp, soft_delete and version attributes cannot be used on a not_query_param field");
+        assert_eq!(parse(&schema).unwrap_err().to_string(), "schema attributes cannot be used on a not_query_param field");
+    }
+}
@@ -6,6 +6,8 @@ mod entity_wrapper;
 mod props_wrapper;
 mod labels;
 mod prop;
+mod attributes;
+mod cypher;
 use quote::quote;
 use syn::{parse_macro_input, Ident, ItemStruct};
 use syn::parse::Parse;
@@ -16,7 +18,7 @@ use syn::parse::Parse;
 /// - An enum that wraps all the struct properties
 /// - Various trait impls, including conversions between the Template and derived version of the struct
 /// All of this is to create an object that is compatible with the Neo4gBuider struct which provides convenient and rusty access to neo4j.
-#[proc_macro_derive(Neo4gNode, attributes(not_query_param, skip_serde))]
+#[proc_macro_derive(Neo4gNode, attributes(not_query_param, skip_serde, neo4g))]
 pub fn neo4g_node_derive(input: TokenStream) -> TokenStream {
     node::generate_neo4g_node(input)
 }
@@ -27,7 +29,7 @@ pub fn neo4g_node_derive(input: TokenStream) -> TokenStream {
 /// - An enum that wraps all the struct properties
 /// - Various trait impls, including conversions between the Template and derived version of the struct
 /// All of this is to create an object that is compatible with the Neo4gBuider struct which provides convenient and rusty access to neo4j.
-#[proc_macro_derive(Neo4gRelation)]
+#[proc_macro_derive(Neo4gRelation, attributes(not_query_param, neo4g))]
 pub fn neo4g_relationship_derive(input: TokenStream) -> TokenStream {
     relation::generate_neo4g_relation(input)
 }
@@ -63,4 +65,22 @@ pub fn neo4g_props_derive(input: TokenStream) -> TokenStream {
 #[proc_macro_derive(Neo4gLabels)]
 pub fn neo4g_labels_derive(input: TokenStream) -> TokenStream {
     labels::generate_labels(input)
+}
+
+/// Builds a query from Cypher pattern syntax, checked against the Neo4gNode and Neo4gRelation derives at compile time.
+/// Node labels and relation types must be derived types, and every property used on an alias must be one of its fields.
+/// $name params are read from variables of the same name in scope. Params used as pattern props go through the entity's Props enum, so their types are checked too.
+/// Extra labels, as in (u:User:SysObj), are checked against the Label enum generated by generate_entity_wrappers!, which has to be in scope.
+/// # Example
+/// ```rust
+/// let name = "admin".to_string();
+/// let (query, params) = cypher!(MATCH (u: User {name: $name})-[m: MemberOf]->(g: Group) RETURN u, g);
+/// ```
+/// The example above expands to the same (String, HashMap<String, BoltType>) that .build() returns:
+/// ```rust
+/// MATCH (u:User {name: $name})-[m:MEMBER_OF]->(g:Group) RETURN u, g
+/// ```
+#[proc_macro]
+pub fn cypher(input: TokenStream) -> TokenStream {
+    cypher::generate_cypher(input)
 }
\ No newline at end of file
@@ -2,6 +2,7 @@ use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
 use syn::{parse_macro_input, DeriveInput, Data, Fields};
 use crate::generators;
+use crate::attributes;
 use heck::ToPascalCase;
 
 pub fn generate_neo4g_relation(input: TokenStream) -> TokenStream {
@@ -225,7 +226,7 @@ pub fn generate_neo4g_relation(input: TokenStream) -> TokenStream {
     }).collect();
     
     // 2. Generate the constructor parameters (using the original types for all fields).
-    let constructor_params: Vec<_> = all_fields_full.iter().map(|field| {
+    let constructor_params: Vec<_> = all_fields_full.iter().filter(|field| attributes::generated_value(field).is_none()).map(|field| {
         let field_ident = field.ident.as_ref().unwrap();
         let field_ty = &field.ty;
     
@@ -253,6 +254,12 @@ pub fn generate_neo4g_relation(input: TokenStream) -> TokenStream {
     let constructor_body: Vec<_> = all_fields_full.iter().map(|field| {
         let field_ident = field.ident.as_ref().unwrap();
         let field_ty = &field.ty;
+        if let Some(generated) = attributes::generated_value(field) {
+            let variant = syn::Ident::new(&field_ident.to_string().to_pascal_case(), field_ident.span());
+            return quote! {
+                #field_ident: #props_enum_name::#variant(#generated)
+            };
+        }
         
         if should_ignore_field(field) {
             quote! {
@@ -403,6 +410,9 @@ let struct_accessor_methods: Vec<_> = all_fields_full.iter().map(|field| {
     let template_constructor_body: Vec<_> = all_fields_full.iter().map(|field| {
         let field_ident = field.ident.as_ref().unwrap();
         let field_ty = &field.ty;
+        if let Some(generated) = attributes::generated_value(field) {
+            return quote! { #field_ident: #generated };
+        }
         // Determine if the field's type is a String.
         let is_string = if let syn::Type::Path(type_path) = field_ty {
             type_path.path.segments.last().map(|seg| seg.ident == "String").unwrap_or(false)
@@ -493,14 +503,14 @@ let struct_accessor_methods: Vec<_> = all_fields_full.iter().map(|field| {
         let from_db_entity_fn = quote! {
             pub fn from_db_entity(db_entity: DbEntityWrapper) -> EntityWrapper {
                 if let DbEntityWrapper::Relation(entity) = db_entity {
-                    EntityWrapper::#new_struct_name(
-                        #new_struct_name {
-                            alias: String::new(),
-                            uuid: Uuid::new_v4(),
-                            entity_type: EntityType::Relation,
-                            #(#field_inits),*
-                        }
-                    )
+                    let mut loaded = #new_struct_name {
+                        alias: String::new(),
+                        uuid: Uuid::new_v4(),
+                        entity_type: EntityType::Relation,
+                        #(#field_inits),*
+                    };
+                    Neo4gEntity::hook_after_load(&mut loaded);
+                    EntityWrapper::#new_struct_name(loaded)
                 } else {
                     EntityWrapper::Nothing(Nothing::default())
                 }
@@ -576,6 +586,30 @@ let struct_accessor_methods: Vec<_> = all_fields_full.iter().map(|field| {
         let get_alias_fn = generators::generate_get_alias();
 
     // Assemble the final output.
+    // Collect schema options from #[neo4g(...)] field attributes.
+    let field_attrs = match attributes::parse_all_field_attrs(&all_fields_full) {
+        Ok(field_attrs) => field_attrs,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    // Generate the methods for the fields marked created_at, updated_at, deleted_at, soft_delete, version and id.
+    let managed_field_fns = match generators::generate_managed_field_fns(&props_enum_name, &all_fields_full) {
+        Ok(fns) => fns,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let key_fields: Vec<syn::Ident> = attributes::key_fields(&field_attrs).iter().map(|name| syn::Ident::new(name, struct_name.span())).collect();
+    let schema_fn = generators::generate_schema(&syn::Ident::new("get_relation_label", proc_macro2::Span::call_site()), &quote! { ::neo4g::query_builder::EntityType::Relation }, &field_attrs);
+
+    // Generate the hook methods for structs marked #[neo4g(hooks)].
+    let struct_attrs = match attributes::parse_struct_attrs(&input.attrs) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    if struct_attrs.repository {
+        return syn::Error::new_spanned(struct_name, "#[neo4g(repository)] is only supported on nodes").to_compile_error().into();
+    }
+    let hook_fns = struct_attrs.hook_fns();
+
     let expanded = quote! {
         // Generated Props enum.
         #conditional_attr
@@ -593,6 +627,9 @@ let struct_accessor_methods: Vec<_> = all_fields_full.iter().map(|field| {
                     _ => ("nope", 0.into()),
                 }
             }
+            fn entity_label(&self) -> Option<String> {
+                Some(#new_struct_name::get_relation_label())
+            }
         }
 
         #conditional_attr
@@ -632,6 +669,18 @@ let struct_accessor_methods: Vec<_> = all_fields_full.iter().map(|field| {
             fn create_from_self(&self) -> (String, std::collections::HashMap<String, BoltType>) {
                 self.create_relation_from_self()
             }
+
+            fn get_props(&self) -> Vec<Self::Props> {
+                self.self_to_props()
+            }
+
+            fn key_props(&self) -> Vec<Self::Props> {
+                vec![#(self.#key_fields.clone()),*]
+            }
+
+            #managed_field_fns
+
+            #hook_fns
         }
 
         #conditional_attr
@@ -661,6 +710,7 @@ let struct_accessor_methods: Vec<_> = all_fields_full.iter().map(|field| {
             #relation_by_fn
             #create_relation_from_self_fn
             #get_relation_label_fn
+            #schema_fn
             #set_alias_fn
             #get_alias_fn
             #self_to_props_fn